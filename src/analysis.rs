@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{Optimize, Spec};
+
+// longest process cycle looked for, cycles grow exponentially with it
+const MAX_CYCLE_LEN: usize = 4;
+
+/*
+structural findings about a spec, quantities are only looked at to decide
+whether the initial stock is enough for a need that nothing produces
+every process id is an index of Spec::processes
+*/
+#[derive(Debug, Default)]
+pub struct Analysis {
+    // fireable[pid] is false when the process can never start, whatever the schedule
+    pub fireable: Vec<bool>,
+    // stocks produced by at least one fireable process
+    pub produced: HashSet<String>,
+    // stocks that can hold something at some point, from the initial stocks or a fireable process
+    pub available: HashSet<String>,
+    // stocks no fireable process produces, with the processes that would but never fire
+    pub unproducible: Vec<(String, Vec<usize>)>,
+    // optimize targets and whether they can ever be produced
+    pub targets: Vec<(String, bool)>,
+    // cycles of processes whose results feed the next one and that together neither gain nor lose anything
+    pub zero_net_cycles: Vec<Vec<usize>>,
+    // stocks every process using them gives back, like a frying pan
+    pub renewable: Vec<String>,
+}
+
+/*
+a stock is renewable when it is needed by at least one process
+and every process needing it gives back exactly what it took
+a process giving back more, like (a:1):(a:2), makes some, so the stock is not renewable
+*/
+pub fn renewable_stocks(spec: &Spec) -> HashSet<String> {
+    let mut users: HashMap<&str, bool> = HashMap::new();
+    for p in &spec.processes {
+        for n in &p.needs {
+            let handed_back = p
+                .results
+                .iter()
+                .any(|r| r.name == n.name && r.quantity == n.quantity);
+            let all = users.entry(n.name.as_str()).or_insert(true);
+            *all = *all && handed_back;
+        }
+    }
+
+    users
+        .into_iter()
+        .filter(|(_, all)| *all)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/*
+a need is met when the initial stock is enough for it, or some fireable process produces it
+a process is fireable when all its needs are met
+grows the fireable set until nothing changes
+*/
+fn fireable_processes(spec: &Spec) -> (Vec<bool>, HashSet<String>) {
+    let mut fireable = vec![false; spec.processes.len()];
+    let mut produced: HashSet<String> = HashSet::new();
+
+    loop {
+        let mut changed = false;
+        for (pid, p) in spec.processes.iter().enumerate() {
+            if fireable[pid] {
+                continue;
+            }
+            let met = p.needs.iter().all(|n| {
+                produced.contains(&n.name)
+                    || spec.init_stocks.get(&n.name).copied().unwrap_or(0) >= n.quantity
+            });
+            if met {
+                fireable[pid] = true;
+                changed = true;
+                for r in &p.results {
+                    produced.insert(r.name.clone());
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    (fireable, produced)
+}
+
+fn net_effect<'a>(spec: &'a Spec, cycle: &[usize]) -> BTreeMap<&'a str, i64> {
+    let mut net: BTreeMap<&'a str, i64> = BTreeMap::new();
+    for &pid in cycle {
+        let p = &spec.processes[pid];
+        for n in &p.needs {
+            *net.entry(n.name.as_str()).or_insert(0) -= n.quantity;
+        }
+        for r in &p.results {
+            *net.entry(r.name.as_str()).or_insert(0) += r.quantity;
+        }
+    }
+    net
+}
+
+/*
+elementary cycles of the process graph, where p -> q when p produces something q needs
+each cycle is only listed once, starting from its smallest process id
+*/
+fn process_cycles(spec: &Spec) -> Vec<Vec<usize>> {
+    let feeds: Vec<Vec<usize>> = spec
+        .processes
+        .iter()
+        .map(|p| {
+            spec.processes
+                .iter()
+                .enumerate()
+                .filter(|(_, q)| {
+                    p.results
+                        .iter()
+                        .any(|r| q.needs.iter().any(|n| n.name == r.name))
+                })
+                .map(|(qid, _)| qid)
+                .collect()
+        })
+        .collect();
+
+    fn walk(
+        feeds: &[Vec<usize>],
+        start: usize,
+        path: &mut Vec<usize>,
+        cycles: &mut Vec<Vec<usize>>,
+    ) {
+        let last = *path.last().unwrap();
+        for &next in &feeds[last] {
+            if next == start {
+                cycles.push(path.clone());
+            } else if next > start && !path.contains(&next) && path.len() < MAX_CYCLE_LEN {
+                path.push(next);
+                walk(feeds, start, path, cycles);
+                path.pop();
+            }
+        }
+    }
+
+    let mut cycles = vec![];
+    for start in 0..spec.processes.len() {
+        walk(&feeds, start, &mut vec![start], &mut cycles);
+    }
+    cycles
+}
+
+pub fn analyze(spec: &Spec) -> Analysis {
+    let (fireable, produced) = fireable_processes(spec);
+
+    let mut available: HashSet<String> = spec
+        .init_stocks
+        .iter()
+        .filter(|(_, qty)| **qty > 0)
+        .map(|(name, _)| name.clone())
+        .collect();
+    available.extend(produced.iter().cloned());
+
+    let mut producers: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (pid, p) in spec.processes.iter().enumerate() {
+        for r in &p.results {
+            producers.entry(r.name.as_str()).or_default().push(pid);
+        }
+    }
+
+    let unproducible = spec
+        .stock_names
+        .iter()
+        .filter(|name| !produced.contains(name.as_str()))
+        .map(|name| {
+            let dead = producers.get(name.as_str()).cloned().unwrap_or_default();
+            (name.to_string(), dead)
+        })
+        .collect();
+
+    let targets = match &spec.optimize {
+        Optimize::Quantity(names) | Optimize::Time(names) => names
+            .iter()
+            .map(|name| (name.clone(), available.contains(name)))
+            .collect(),
+    };
+
+    let zero_net_cycles = process_cycles(spec)
+        .into_iter()
+        .filter(|cycle| cycle.iter().all(|&pid| fireable[pid]))
+        .filter(|cycle| net_effect(spec, cycle).values().all(|&qty| qty == 0))
+        .collect();
+
+    let renewable_names = renewable_stocks(spec);
+    let renewable = spec
+        .stock_names
+        .iter()
+        .filter(|name| renewable_names.contains(*name))
+        .cloned()
+        .collect();
+
+    Analysis {
+        fireable,
+        produced,
+        available,
+        unproducible,
+        targets,
+        zero_net_cycles,
+        renewable,
+    }
+}
+
+fn names(spec: &Spec, pids: &[usize]) -> String {
+    pids.iter()
+        .map(|&pid| spec.processes[pid].name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn format_report(spec: &Spec, analysis: &Analysis) -> String {
+    let mut out = String::new();
+
+    out += "Dead processes (can never start) :\n";
+    let mut any = false;
+    for (pid, p) in spec.processes.iter().enumerate() {
+        if analysis.fireable[pid] {
+            continue;
+        }
+        any = true;
+        let missing: Vec<&str> = p
+            .needs
+            .iter()
+            .filter(|n| {
+                !analysis.produced.contains(&n.name)
+                    && spec.init_stocks.get(&n.name).copied().unwrap_or(0) < n.quantity
+            })
+            .map(|n| n.name.as_str())
+            .collect();
+        out += &format!("  {} : missing {}\n", p.name, missing.join(", "));
+    }
+    if !any {
+        out += "  (none)\n";
+    }
+
+    out += "Stocks never produced :\n";
+    if analysis.unproducible.is_empty() {
+        out += "  (none)\n";
+    }
+    for (name, dead) in &analysis.unproducible {
+        let initial = spec.init_stocks.get(name).copied().unwrap_or(0);
+        if dead.is_empty() {
+            out += &format!(
+                "  {} : no process produces it, initial stock {}\n",
+                name, initial
+            );
+        } else {
+            out += &format!(
+                "  {} : only produced by dead processes {}, initial stock {}\n",
+                name,
+                names(spec, dead),
+                initial
+            );
+        }
+    }
+
+    out += "Optimize targets :\n";
+    for (name, reachable) in &analysis.targets {
+        if *reachable {
+            out += &format!("  {} : reachable\n", name);
+        } else {
+            out += &format!("  {} : UNREACHABLE\n", name);
+        }
+    }
+
+    out += "Zero-net cycles (processes undoing each other) :\n";
+    if analysis.zero_net_cycles.is_empty() {
+        out += "  (none)\n";
+    }
+    for cycle in &analysis.zero_net_cycles {
+        out += &format!("  {}\n", names(spec, cycle));
+    }
+
+    out += "Renewable stocks (given back by every process using them) :\n";
+    if analysis.renewable.is_empty() {
+        out += "  (none)\n";
+    }
+    for name in &analysis.renewable {
+        out += &format!("  {}\n", name);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimSpec, parse};
+
+    #[test]
+    fn frying_pan_is_renewable() {
+        let spec = parse(include_str!("../input_files/steak")).unwrap();
+        let renewable = renewable_stocks(&spec);
+        assert_eq!(renewable, HashSet::from(["poele".to_string()]));
+
+        // cuisson_1 only really makes steak_mi_cuit
+        let sim = SimSpec::from_spec(&spec);
+        let mi_cuit = sim.stock_names.iter().position(|n| n == "steak_mi_cuit");
+        assert_eq!(sim.gains[0], vec![(mi_cuit.unwrap(), 2)]);
+    }
+
+    #[test]
+    fn multiplier_is_not_renewable() {
+        let spec =
+            parse("a:1\nb:1\ndouble:(a:1):(a:2):1\nuse:(b:1):(b:1;c:1):1\noptimize:(a)\n").unwrap();
+        assert_eq!(renewable_stocks(&spec), HashSet::from(["b".to_string()]));
+
+        // the doubling keeps its whole result, the surplus is what it makes
+        let sim = SimSpec::from_spec(&spec);
+        assert_eq!(sim.gains[0], vec![(0, 2)]);
+    }
+
+    // stuck needs x that nothing makes, after only runs on what stuck makes, big needs more a than there is
+    const DEAD_END: &str = "a:2\nstart:(a:1):(b:1):1\nchain:(b:1):(c:1):1\nstuck:(x:1;a:1):(d:1):1\nafter:(d:1):(e:1):1\nbig:(a:5):(f:1):1\noptimize:(c;e)\n";
+
+    #[test]
+    fn fireable_grows_through_what_fireable_processes_make() {
+        let spec = parse(DEAD_END).unwrap();
+        let analysis = analyze(&spec);
+        assert_eq!(analysis.fireable, vec![true, true, false, false, false]);
+        assert_eq!(
+            analysis.produced,
+            HashSet::from(["b".to_string(), "c".to_string()])
+        );
+
+        let report = format_report(&spec, &analysis);
+        assert!(report.contains("  stuck : missing x\n"));
+        assert!(report.contains("  after : missing d\n"));
+        assert!(report.contains("  big : missing a\n"));
+    }
+
+    #[test]
+    fn unproducible_stocks_keep_their_dead_producers() {
+        let spec = parse(DEAD_END).unwrap();
+        let analysis = analyze(&spec);
+        let unproducible: Vec<(&str, Vec<usize>)> = analysis
+            .unproducible
+            .iter()
+            .map(|(name, dead)| (name.as_str(), dead.clone()))
+            .collect();
+        assert_eq!(
+            unproducible,
+            vec![
+                ("a", vec![]),
+                ("x", vec![]),
+                ("d", vec![2]),
+                ("e", vec![3]),
+                ("f", vec![4])
+            ]
+        );
+
+        let report = format_report(&spec, &analysis);
+        assert!(report.contains("  x : no process produces it, initial stock 0\n"));
+        assert!(report.contains("  d : only produced by dead processes stuck, initial stock 0\n"));
+    }
+
+    #[test]
+    fn target_made_only_by_dead_processes_is_unreachable() {
+        let spec = parse(DEAD_END).unwrap();
+        let analysis = analyze(&spec);
+        assert_eq!(
+            analysis.targets,
+            vec![("c".to_string(), true), ("e".to_string(), false)]
+        );
+        assert!(format_report(&spec, &analysis).contains("  e : UNREACHABLE\n"));
+
+        // an initial stock is reachable even when nothing makes it
+        let spec = parse("a:1\nb:1\np:(b:1):(c:1):1\noptimize:(a)\n").unwrap();
+        assert_eq!(analyze(&spec).targets, vec![("a".to_string(), true)]);
+    }
+
+    #[test]
+    fn egg_separation_undoes_the_reunion() {
+        let spec = parse(include_str!("../input_files/pomme")).unwrap();
+        let pid = |name: &str| spec.processes.iter().position(|p| p.name == name).unwrap();
+        let analysis = analyze(&spec);
+        assert_eq!(
+            analysis.zero_net_cycles,
+            vec![vec![pid("separation_oeuf"), pid("reunion_oeuf")]]
+        );
+
+        // a cycle that makes something is not listed
+        let spec =
+            parse("a:1\nsplit:(a:1):(b:1;c:1):1\njoin:(b:1;c:1):(a:2):1\noptimize:(a)\n").unwrap();
+        assert!(analyze(&spec).zero_net_cycles.is_empty());
+    }
+}
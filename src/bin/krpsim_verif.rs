@@ -0,0 +1,51 @@
+use std::env;
+use std::fs;
+use std::process::exit;
+
+use krpsim::verif::verify;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!(
+            "Usage : cargo run --release --bin krpsim_verif -- input_file_path trace_file_path"
+        );
+        std::process::exit(0);
+    }
+
+    let read = |path: &str| {
+        fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read the contents of the file {} : {}", path, e);
+            exit(1);
+        })
+    };
+
+    let contents = read(&args[1]);
+    let trace = read(&args[2]);
+
+    let spec = krpsim::parse(&contents).unwrap_or_else(|errors| {
+        for e in &errors {
+            eprintln!("{}\n", e.render(&contents));
+        }
+        eprintln!(
+            "Error while parsing the contents of the file : {} error(s)",
+            errors.len()
+        );
+        exit(1);
+    });
+
+    let report = verify(&spec, &trace).unwrap_or_else(|e| {
+        eprintln!("Invalid trace : {}", e);
+        exit(1);
+    });
+
+    println!("Trace is valid, {} process starts", report.starts);
+    println!("last start : cycle {}", report.last_start);
+    println!("every job done : cycle {}", report.last_finish);
+
+    // same order as the stocks printed by KRPSim, once every job is done
+    println!("Stock :");
+    for name in &spec.stock_names {
+        println!(" {} => {}", name, report.stocks[name]);
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dashmap::DashMap;
+
+use crate::ga::{Genome, priority_from_keys};
+
+/*
+what the simulator actually reads from a genome
+the whole priority order counts, disabled processes included, since they still weigh on the deficits
+keys of 1.0 are the disabled processes, their exact value otherwise doesn't matter
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Phenotype {
+    order: Vec<u32>,
+    disabled: Vec<bool>,
+    pending_stock_divider: i32,
+}
+
+impl Phenotype {
+    pub fn of(genome: &Genome) -> Self {
+        Self {
+            order: priority_from_keys(&genome.keys)
+                .into_iter()
+                .map(|pid| pid as u32)
+                .collect(),
+            disabled: genome.keys.iter().map(|&k| k == 1.0).collect(),
+            pending_stock_divider: genome.pending_stock_divider,
+        }
+    }
+}
+
+/*
+fitness of the phenotypes already simulated, shared by the islands and the rayon threads
+the simulation is deterministic so a hit gives exactly the fitness a simulation would
+once `capacity` entries are stored the cache starts over, the old schedules are rarely bred again
+*/
+pub struct FitnessCache {
+    map: DashMap<Phenotype, i64>,
+    capacity: usize,
+    hits: AtomicU64,
+    lookups: AtomicU64,
+}
+
+impl FitnessCache {
+    // a capacity of 0 disables the cache
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            map: DashMap::new(),
+            capacity,
+            hits: AtomicU64::new(0),
+            lookups: AtomicU64::new(0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    // sets the fitness of the genome, with `eval` only called on a miss
+    pub fn fitness(&self, genome: &mut Genome, eval: impl FnOnce(&mut Genome) -> i64) -> i64 {
+        if !self.enabled() {
+            return eval(genome);
+        }
+
+        let key = Phenotype::of(genome);
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        if let Some(fitness) = self.map.get(&key).map(|f| *f) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            genome.fitness = fitness;
+            return fitness;
+        }
+
+        let fitness = eval(genome);
+        if self.map.len() >= self.capacity {
+            self.map.clear();
+        }
+        self.map.insert(key, fitness);
+        fitness
+    }
+
+    // (hits, lookups) since the cache was built
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.lookups.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
@@ -0,0 +1,291 @@
+use std::fs;
+
+use crate::SimSpec;
+use crate::config::{GaConfig, RunOptions};
+use crate::ga::{GaState, Genome, Population};
+
+const HEADER: &str = "krpsim checkpoint 2";
+
+/*
+the state of a GA run saved by run_ga, enough to go on with the same result as a run never stopped
+the settings are saved with it since a resumed run must breed the same way
+*/
+pub struct Checkpoint {
+    pub seed: u64,
+    pub horizon: i64,
+    pub config: GaConfig,
+    pub state: GaState,
+}
+
+/*
+plain text, one genome per line as `fitness divider disabled key...`
+floats are written with Display which gives them back exactly when parsed
+
+krpsim checkpoint 2
+seed 7
+horizon 10000
+processes 12
+generation 40
+island 0
+overall_best 13238
+stale_generations 3
+[config]
+top_pct = 0.1
+...
+[island 0]
+best_fitness 13238
+last_improvment 37
+current_reset_value 6
+last_wipe_improvments 1
+best 13238 3 0 0.52 0.17 ...
+genome 13238 3 0 0.52 0.17 ...
+...
+*/
+fn format_genome(tag: &str, g: &Genome) -> String {
+    let mut line = format!(
+        "{} {} {} {}",
+        tag, g.fitness, g.pending_stock_divider, g.disabled_processes as u8
+    );
+    for k in &g.keys {
+        line += &format!(" {}", k);
+    }
+    line.push('\n');
+    line
+}
+
+pub fn format(state: &GaState, options: &RunOptions, config: &GaConfig) -> String {
+    let mut out = format!(
+        "{}\nseed {}\nhorizon {}\nprocesses {}\ngeneration {}\nisland {}\noverall_best {}\nstale_generations {}\n",
+        HEADER,
+        options.seed,
+        options.horizon,
+        state.best_cands.first().map_or(0, |g| g.keys.len()),
+        state.generation,
+        state.island,
+        state.overall_best,
+        state.stale_generations
+    );
+    out += "[config]\n";
+    out += &config.format();
+
+    for (idx, island) in state.pop.candidates.iter().enumerate() {
+        out += &format!(
+            "[island {}]\nbest_fitness {}\nlast_improvment {}\ncurrent_reset_value {}\nlast_wipe_improvments {}\n",
+            idx,
+            state.best_fitness[idx],
+            state.last_improvment[idx],
+            state.current_reset_value[idx],
+            state.last_wipe_improvments[idx] as u8
+        );
+        out += &format_genome("best", &state.best_cands[idx]);
+        for g in island {
+            out += &format_genome("genome", g);
+        }
+    }
+    out
+}
+
+// written next to the file then renamed, so a run killed while saving keeps the last checkpoint
+pub fn save(
+    path: &str,
+    state: &GaState,
+    options: &RunOptions,
+    config: &GaConfig,
+) -> Result<(), String> {
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, format(state, options, config))
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to write the checkpoint {} : {}", path, e))
+}
+
+struct Lines<'a> {
+    it: std::iter::Peekable<std::iter::Enumerate<std::str::Lines<'a>>>,
+}
+
+impl<'a> Lines<'a> {
+    fn next(&mut self) -> Result<(usize, &'a str), String> {
+        self.it
+            .next()
+            .map(|(nbr, line)| (nbr + 1, line.trim()))
+            .ok_or_else(|| "unexpected end of file".to_string())
+    }
+
+    // the value of a `key value` line
+    fn value<T: std::str::FromStr>(&mut self, key: &str) -> Result<T, String> {
+        let (nbr, line) = self.next()?;
+        line.strip_prefix(key)
+            .and_then(|v| v.strip_prefix(' '))
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| format!("line {} : expected {} <value>", nbr, key))
+    }
+
+    fn genome(&mut self, tag: &str, process_count: usize) -> Result<Genome, String> {
+        let (nbr, line) = self.next()?;
+        let err = || format!("line {} : invalid {} line", nbr, tag);
+        let mut words = line.split_whitespace();
+        if words.next() != Some(tag) {
+            return Err(err());
+        }
+        let num = |w: Option<&str>| w.and_then(|w| w.parse::<i64>().ok()).ok_or_else(err);
+        let fitness = num(words.next())?;
+        let divider = num(words.next())? as i32;
+        let disabled = num(words.next())? != 0;
+        let keys = words
+            .map(|w| w.parse::<f64>().map_err(|_| err()))
+            .collect::<Result<Vec<f64>, String>>()?;
+        if keys.len() != process_count {
+            return Err(format!(
+                "line {} : {} keys for {} processes",
+                nbr,
+                keys.len(),
+                process_count
+            ));
+        }
+        Ok(Genome::new(keys, fitness, divider, disabled))
+    }
+}
+
+pub fn parse(input: &str, spec: &SimSpec) -> Result<Checkpoint, String> {
+    let mut lines = Lines {
+        it: input.lines().enumerate().peekable(),
+    };
+    if lines.next()?.1 != HEADER {
+        return Err("not a krpsim checkpoint".to_string());
+    }
+    let seed = lines.value("seed")?;
+    let horizon = lines.value("horizon")?;
+    let process_count: usize = lines.value("processes")?;
+    if process_count != spec.process_count() {
+        return Err(format!(
+            "the checkpoint has {} processes, the scenario {}",
+            process_count,
+            spec.process_count()
+        ));
+    }
+    let generation = lines.value("generation")?;
+    let island = lines.value("island")?;
+    let overall_best = lines.value("overall_best")?;
+    let stale_generations = lines.value("stale_generations")?;
+
+    let (nbr, line) = lines.next()?;
+    if line != "[config]" {
+        return Err(format!("line {} : expected [config]", nbr));
+    }
+    let mut config_lines = String::new();
+    while let Some((_, line)) = lines.it.next_if(|(_, l)| !l.starts_with('[')) {
+        config_lines += line;
+        config_lines.push('\n');
+    }
+    let config = GaConfig::parse(&config_lines)?;
+    config.validate()?;
+    if island >= config.islands_count {
+        return Err(format!("island {} out of {}", island, config.islands_count));
+    }
+
+    let islands_count = config.islands_count;
+    let mut state = GaState {
+        generation,
+        island,
+        pop: Population::default(),
+        best_cands: vec![],
+        last_improvment: vec![],
+        best_fitness: vec![],
+        current_reset_value: vec![],
+        last_wipe_improvments: vec![],
+        overall_best,
+        stale_generations,
+    };
+    for idx in 0..islands_count {
+        let (nbr, line) = lines.next()?;
+        if line != format!("[island {}]", idx) {
+            return Err(format!("line {} : expected [island {}]", nbr, idx));
+        }
+        state.best_fitness.push(lines.value("best_fitness")?);
+        state.last_improvment.push(lines.value("last_improvment")?);
+        state
+            .current_reset_value
+            .push(lines.value("current_reset_value")?);
+        state
+            .last_wipe_improvments
+            .push(lines.value::<u8>("last_wipe_improvments")? != 0);
+        state.best_cands.push(lines.genome("best", process_count)?);
+        let mut island = vec![];
+        for _ in 0..config.population_per_island() {
+            island.push(lines.genome("genome", process_count)?);
+        }
+        state.pop.candidates.push(island);
+    }
+    if let Some((nbr, _)) = lines.it.find(|(_, l)| !l.trim().is_empty()) {
+        return Err(format!("line {} : unexpected content", nbr + 1));
+    }
+
+    Ok(Checkpoint {
+        seed,
+        horizon,
+        config,
+        state,
+    })
+}
+
+pub fn load(path: &str, spec: &SimSpec) -> Result<Checkpoint, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read the checkpoint {} : {}", path, e))?;
+    parse(&contents, spec).map_err(|e| format!("Invalid checkpoint {} : {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::{GaState, gen_initial_pop};
+
+    fn sim_spec(input: &str) -> SimSpec {
+        SimSpec::from_spec(&crate::parse(input).expect("invalid scenario"))
+    }
+
+    #[test]
+    fn a_checkpoint_reads_back_as_it_was_saved() {
+        let spec = sim_spec(include_str!("../input_files/steak"));
+        let config = GaConfig {
+            max_population: 40,
+            islands_count: 2,
+            ..GaConfig::default()
+        };
+        let options = RunOptions {
+            seed: 42,
+            horizon: 100,
+            ..RunOptions::default()
+        };
+        let pop = gen_initial_pop(spec.process_count(), &config, options.seed);
+        let state = GaState::new(&spec, pop, &config, options.horizon);
+
+        let saved = format(&state, &options, &config);
+        let checkpoint = parse(&saved, &spec).unwrap();
+        assert_eq!(checkpoint.seed, 42);
+        assert_eq!(checkpoint.horizon, 100);
+        assert_eq!(checkpoint.state.best_fitness, state.best_fitness);
+        assert_eq!(
+            format(&checkpoint.state, &options, &checkpoint.config),
+            saved
+        );
+    }
+
+    #[test]
+    fn another_scenario_is_refused() {
+        let spec = sim_spec(include_str!("../input_files/steak"));
+        let config = GaConfig {
+            max_population: 40,
+            islands_count: 1,
+            ..GaConfig::default()
+        };
+        let options = RunOptions::default();
+        let pop = gen_initial_pop(spec.process_count(), &config, options.seed);
+        let saved = format(
+            &GaState::new(&spec, pop, &config, options.horizon),
+            &options,
+            &config,
+        );
+
+        let other = sim_spec(include_str!("../input_files/simple"));
+        assert!(parse(&saved, &other).is_err());
+    }
+}
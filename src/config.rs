@@ -0,0 +1,338 @@
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use crate::decoder::{DEFAULT_MAX_GREEDY_STARTS, Decoder, DecoderKind};
+use crate::exact::DEFAULT_MAX_NODES;
+use crate::ga::{DEFAULT_GENERATIONS, MAX_CYCLES, Score};
+use crate::trajectory::{Cooling, OptimizerKind};
+
+pub const DEFAULT_CHECKPOINT_EVERY: usize = 10;
+
+/*
+knobs of the genetic algorithm, and of the single trajectory optimizers (sa_ and tabu_ keys)
+can be loaded from a file of `key = value` lines, which also reads a flat TOML file,
+and every key can be overridden from the command line with --key=value
+*/
+#[derive(Debug, Clone)]
+pub struct GaConfig {
+    // share of each island kept as is and used as first parent
+    pub top_pct: f64,
+    // share of each island replaced by random genomes every generation
+    pub bot_pct: f64,
+    // chance for a child to take each key from its first parent
+    pub head_pct: f64,
+    // total population, split evenly between the islands
+    pub max_population: usize,
+    pub islands_count: usize,
+    // generations without improvement before an island is wiped, at first
+    pub reset_value_gen: i64,
+    // the wait before a wipe grows by generation / (population per island / reset_divider)
+    pub reset_divider: i64,
+    pub max_reset_value: i64,
+    pub mut_chance_swap_process: f64,
+    // chances for a child to get one more process disabled, or one disabled process back
+    pub mut_chance_disable_process: f64,
+    pub mut_chance_enable_process: f64,
+    // phenotypes whose fitness is remembered, 0 simulates every genome
+    pub cache_size: usize,
+    // how a genome becomes a schedule, see decoder
+    pub decoder: DecoderKind,
+    // job starts after which the serial and parallel decoders stop starting, 0 for no limit
+    pub max_greedy_starts: usize,
+    // neighbours of the elites tried by the local search on each island every generation, 0 for none
+    pub local_search_evals: usize,
+    // temperatures of the annealing at the start and at the end, as a share of the current fitness
+    pub sa_temperature: f64,
+    pub sa_final_temperature: f64,
+    // how the temperature goes from one to the other over the evaluation budget
+    pub sa_cooling: Cooling,
+    // iterations a process stays tabu once a move touched it
+    pub tabu_tenure: usize,
+}
+
+impl Default for GaConfig {
+    fn default() -> Self {
+        Self {
+            top_pct: 0.1,
+            bot_pct: 0.2,
+            head_pct: 0.5,
+            max_population: 400,
+            islands_count: 8,
+            reset_value_gen: 2,
+            reset_divider: 8,
+            max_reset_value: 20,
+            mut_chance_swap_process: 0.10,
+            mut_chance_disable_process: 0.05,
+            mut_chance_enable_process: 0.05,
+            cache_size: 1_000_000,
+            decoder: DecoderKind::Heuristic,
+            max_greedy_starts: DEFAULT_MAX_GREEDY_STARTS,
+            local_search_evals: 0,
+            sa_temperature: 0.05,
+            sa_final_temperature: 0.0005,
+            sa_cooling: Cooling::Geometric,
+            tabu_tenure: 7,
+        }
+    }
+}
+
+const KEYS: [&str; 19] = [
+    "top_pct",
+    "bot_pct",
+    "head_pct",
+    "max_population",
+    "islands_count",
+    "reset_value_gen",
+    "reset_divider",
+    "max_reset_value",
+    "mut_chance_swap_process",
+    "mut_chance_disable_process",
+    "mut_chance_enable_process",
+    "cache_size",
+    "decoder",
+    "max_greedy_starts",
+    "local_search_evals",
+    "sa_temperature",
+    "sa_final_temperature",
+    "sa_cooling",
+    "tabu_tenure",
+];
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {} : {}", key, value))
+}
+
+impl GaConfig {
+    pub fn population_per_island(&self) -> usize {
+        self.max_population / self.islands_count
+    }
+
+    // the decoder of the run, with its limit on job starts
+    pub fn decoder(&self) -> Box<dyn Decoder> {
+        self.decoder.decoder(self.max_greedy_starts)
+    }
+
+    // dashes are accepted in place of underscores so that --top-pct works as well as --top_pct
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let key = key.replace('-', "_");
+        let value = value.trim().trim_matches('"');
+        match key.as_str() {
+            "top_pct" => self.top_pct = parse_value(&key, value)?,
+            "bot_pct" => self.bot_pct = parse_value(&key, value)?,
+            "head_pct" => self.head_pct = parse_value(&key, value)?,
+            "max_population" => self.max_population = parse_value(&key, value)?,
+            "islands_count" => self.islands_count = parse_value(&key, value)?,
+            "reset_value_gen" => self.reset_value_gen = parse_value(&key, value)?,
+            "reset_divider" => self.reset_divider = parse_value(&key, value)?,
+            "max_reset_value" => self.max_reset_value = parse_value(&key, value)?,
+            "mut_chance_swap_process" => self.mut_chance_swap_process = parse_value(&key, value)?,
+            "mut_chance_disable_process" => {
+                self.mut_chance_disable_process = parse_value(&key, value)?
+            }
+            "mut_chance_enable_process" => {
+                self.mut_chance_enable_process = parse_value(&key, value)?
+            }
+            "cache_size" => self.cache_size = parse_value(&key, value)?,
+            "decoder" => self.decoder = DecoderKind::parse(value)?,
+            "max_greedy_starts" => self.max_greedy_starts = parse_value(&key, value)?,
+            "local_search_evals" => self.local_search_evals = parse_value(&key, value)?,
+            "sa_temperature" => self.sa_temperature = parse_value(&key, value)?,
+            "sa_final_temperature" => self.sa_final_temperature = parse_value(&key, value)?,
+            "sa_cooling" => self.sa_cooling = Cooling::parse(value)?,
+            "tabu_tenure" => self.tabu_tenure = parse_value(&key, value)?,
+            _ => return Err(format!("Unknown GA setting {}", key)),
+        }
+        Ok(())
+    }
+
+    // the settings as `key = value` lines, which GaConfig::parse reads back
+    pub fn format(&self) -> String {
+        format!(
+            "top_pct = {}\nbot_pct = {}\nhead_pct = {}\nmax_population = {}\nislands_count = {}\nreset_value_gen = {}\nreset_divider = {}\nmax_reset_value = {}\nmut_chance_swap_process = {}\nmut_chance_disable_process = {}\nmut_chance_enable_process = {}\ncache_size = {}\ndecoder = {}\nmax_greedy_starts = {}\nlocal_search_evals = {}\nsa_temperature = {}\nsa_final_temperature = {}\nsa_cooling = {}\ntabu_tenure = {}\n",
+            self.top_pct,
+            self.bot_pct,
+            self.head_pct,
+            self.max_population,
+            self.islands_count,
+            self.reset_value_gen,
+            self.reset_divider,
+            self.max_reset_value,
+            self.mut_chance_swap_process,
+            self.mut_chance_disable_process,
+            self.mut_chance_enable_process,
+            self.cache_size,
+            self.decoder,
+            self.max_greedy_starts,
+            self.local_search_evals,
+            self.sa_temperature,
+            self.sa_final_temperature,
+            self.sa_cooling,
+            self.tabu_tenure
+        )
+    }
+
+    pub fn is_key(key: &str) -> bool {
+        KEYS.contains(&key.replace('-', "_").as_str())
+    }
+
+    /*
+    blank lines, `#` comments and `[section]` headers are skipped
+    keys missing from the file keep their default value
+    */
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut config = GaConfig::default();
+
+        for (line_nbr, line) in input.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {} : expected key = value", line_nbr + 1))?;
+
+            config
+                .set(key.trim(), value)
+                .map_err(|e| format!("line {} : {}", line_nbr + 1, e))?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the config file {} : {}", path, e))?;
+        GaConfig::parse(&contents)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, pct) in [
+            ("top_pct", self.top_pct),
+            ("bot_pct", self.bot_pct),
+            ("head_pct", self.head_pct),
+            ("mut_chance_swap_process", self.mut_chance_swap_process),
+            (
+                "mut_chance_disable_process",
+                self.mut_chance_disable_process,
+            ),
+            ("mut_chance_enable_process", self.mut_chance_enable_process),
+        ] {
+            if !(0.0..=1.0).contains(&pct) {
+                return Err(format!("{} must be between 0 and 1 : {}", name, pct));
+            }
+        }
+
+        if self.islands_count == 0 {
+            return Err("islands_count must be at least 1".to_string());
+        }
+
+        if self.population_per_island() < 2 {
+            return Err(format!(
+                "max_population must give at least 2 genomes per island : {} for {} islands",
+                self.max_population, self.islands_count
+            ));
+        }
+
+        if self.reset_divider <= 0 || self.reset_divider > self.population_per_island() as i64 {
+            return Err(format!(
+                "reset_divider must be between 1 and the population per island : {}",
+                self.reset_divider
+            ));
+        }
+
+        if !(self.sa_temperature > 0.0
+            && self.sa_final_temperature > 0.0
+            && self.sa_final_temperature <= self.sa_temperature)
+        {
+            return Err(format!(
+                "the temperatures must be positive and sa_final_temperature at most sa_temperature : {} and {}",
+                self.sa_temperature, self.sa_final_temperature
+            ));
+        }
+
+        if self.reset_value_gen < 0 || self.max_reset_value < 0 {
+            return Err("reset_value_gen and max_reset_value can't be negative".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/*
+parameters of a run that are not GA knobs
+the search stops at the first of its stopping criteria met, a generation count of 0 means no limit
+*/
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub generations: usize,
+    // number of cycles simulated for each genome
+    pub horizon: i64,
+    pub seed: u64,
+    // seconds the search may take, checked after every island
+    pub time_budget: Option<f64>,
+    // stop as soon as a genome makes this quantity of the targets, by that cycle if one is given
+    pub target_fitness: Option<Score>,
+    // best known quantity of the optimize targets, and cycle for time, nothing can do better once it is reached
+    pub optimum: Option<Score>,
+    // stop after this many generations without any island improving
+    pub stagnation: Option<usize>,
+    // file the state of the search is saved to, see checkpoint
+    pub checkpoint: Option<String>,
+    // generations between two checkpoints, 0 only saves when the search stops
+    pub checkpoint_every: usize,
+    // set from elsewhere, e.g. a Ctrl-C handler, to stop the search after the current island
+    pub interrupt: Option<Arc<AtomicBool>>,
+    // the GA, or a single trajectory optimizer given the evaluations of as many generations
+    pub optimizer: OptimizerKind,
+    // states the exact solver may explore before giving up, 0 for no limit
+    pub max_nodes: usize,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            generations: DEFAULT_GENERATIONS,
+            horizon: MAX_CYCLES,
+            seed: 0,
+            time_budget: None,
+            target_fitness: None,
+            optimum: None,
+            stagnation: None,
+            checkpoint: None,
+            checkpoint_every: DEFAULT_CHECKPOINT_EVERY,
+            interrupt: None,
+            optimizer: OptimizerKind::Ga,
+            max_nodes: DEFAULT_MAX_NODES,
+        }
+    }
+}
+
+impl RunOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.generations == 0
+            && self.time_budget.is_none()
+            && self.target_fitness.is_none()
+            && self.optimum.is_none()
+            && self.stagnation.is_none()
+        {
+            return Err("Nothing would stop a run without a generation limit".to_string());
+        }
+        if self.time_budget.is_some_and(|t| t.is_nan() || t <= 0.0) {
+            return Err("The time budget must be positive".to_string());
+        }
+        if self.optimizer != OptimizerKind::Ga && self.checkpoint.is_some() {
+            return Err(format!(
+                "Only the genetic algorithm can be checkpointed, not {}",
+                self.optimizer
+            ));
+        }
+        if self.stagnation == Some(0) {
+            return Err("stagnation must be at least 1 generation".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+
+use crate::Optimize;
+use crate::SimSpec;
+use crate::ga::{Genome, Sim, inputs_available, priority_from_keys, run_events};
+use crate::logger::Logger;
+
+/*
+turns a genome into a schedule, the processes it starts at every cycle, and scores it
+every decoder reads the priority order from the keys and never starts a disabled process (key 1.0),
+the divider is only read by the heuristic
+*/
+pub trait Decoder: Sync {
+    fn name(&self) -> &'static str;
+
+    // simulates the genome for `horizon` cycles, recording the job starts in Sim::trace if `record`
+    fn decode(
+        &self,
+        spec: &SimSpec,
+        genome: &Genome,
+        horizon: i64,
+        record: bool,
+        logger: Option<&mut Logger>,
+    ) -> (i64, Sim);
+}
+
+/*
+a process that takes nothing could start forever within a cycle,
+the greedy decoders start it once per cycle instead
+*/
+pub(crate) fn consumes(spec: &SimSpec, pid: usize) -> bool {
+    spec.needs[pid].iter().any(|n| n.1 > 0)
+}
+
+/*
+starting everything it can, a greedy decoder can run into tens of millions of jobs
+when a loop multiplies a stock (money in pomme, clocks in inception), taking a minute a genome
+past max_starts starts it lets the running jobs finish without starting new ones, 0 for no limit
+the limit is a GA setting, max_greedy_starts
+*/
+pub const DEFAULT_MAX_GREEDY_STARTS: usize = 50_000;
+
+fn start_budget(max_starts: usize) -> usize {
+    if max_starts == 0 {
+        usize::MAX
+    } else {
+        max_starts
+    }
+}
+
+/*
+the decoder the GA was built around
+a process only starts when one of its results is lacking for a higher priority process,
+what is already being produced counting as pending / pending_stock_divider
+*/
+pub struct Heuristic;
+
+fn deficits_for_higher_priority(
+    order: &[usize],
+    pos: usize,
+    spec: &SimSpec,
+    stocks: &[i64],
+    deficit: &mut [i64],
+) {
+    for &hp_idx in order[pos..=pos].iter() {
+        // handing a tool back is not producing it, so a frying pan is never stockpiled for its own sake
+        if pos == 0 {
+            // every target is wanted, not only the ones the top process makes
+            for &stock_id in &spec.target_stock_ids {
+                deficit[stock_id] = i64::MAX;
+            }
+            for result in &spec.gains[hp_idx] {
+                let stock_id = result.0;
+                deficit[stock_id] = i64::MAX;
+            }
+            if let Optimize::Quantity(_) = spec.optimize {
+                for need in &spec.needs[hp_idx] {
+                    let stock_id = need.0;
+                    deficit[stock_id] = i64::MAX;
+                }
+            }
+        }
+
+        if inputs_available(&spec.needs[hp_idx], stocks) {
+            continue;
+        }
+
+        for &(stock_id, need_qty) in &spec.needs[hp_idx] {
+            let have = stocks[stock_id];
+            let d = need_qty - have;
+            if d > 0 && deficit[stock_id] != i64::MAX {
+                deficit[stock_id] = deficit[stock_id].saturating_add(d);
+            }
+        }
+    }
+}
+
+impl Decoder for Heuristic {
+    fn name(&self) -> &'static str {
+        "heuristic"
+    }
+
+    fn decode(
+        &self,
+        spec: &SimSpec,
+        cand: &Genome,
+        horizon: i64,
+        record: bool,
+        logger: Option<&mut Logger>,
+    ) -> (i64, Sim) {
+        let order = priority_from_keys(&cand.keys);
+        let keys = &cand.keys;
+        let divider = cand.pending_stock_divider as i64;
+        let mut deficit = vec![0; spec.init_stocks.len()];
+
+        run_events(spec, horizon, logger, |s| {
+            deficit.fill(0);
+            deficits_for_higher_priority(&order, 0, spec, &s.stocks, &mut deficit);
+
+            for (pos, &pid) in order.iter().enumerate() {
+                if pos > 1 {
+                    deficits_for_higher_priority(&order, pos - 1, spec, &s.stocks, &mut deficit);
+                }
+
+                if keys[pid] == 1.0 {
+                    continue;
+                }
+
+                if !s.can_start(spec, pid) {
+                    continue;
+                }
+
+                // sinks produce nothing, so they only run when no higher priority process lacks what they eat
+                // a process only handing a tool back produces nothing either
+                let should_run = if spec.gains[pid].is_empty() {
+                    pos == 0 || spec.needs[pid].iter().all(|n| deficit[n.0] == 0)
+                } else {
+                    spec.gains[pid].iter().any(|r| {
+                        let stock_id = r.0;
+                        deficit[stock_id] > (s.pending[stock_id] / divider)
+                    })
+                };
+
+                if should_run {
+                    s.start(spec, pid, record);
+                }
+            }
+        })
+    }
+}
+
+/*
+serial schedule generation : at every cycle the highest priority process that can start is started,
+again and again until none can
+starting a job only takes from the stocks, so this runs each process in turn as many times as it can
+*/
+pub struct SerialSgs {
+    pub max_starts: usize,
+}
+
+impl Decoder for SerialSgs {
+    fn name(&self) -> &'static str {
+        "serial"
+    }
+
+    fn decode(
+        &self,
+        spec: &SimSpec,
+        cand: &Genome,
+        horizon: i64,
+        record: bool,
+        logger: Option<&mut Logger>,
+    ) -> (i64, Sim) {
+        let order: Vec<usize> = priority_from_keys(&cand.keys)
+            .into_iter()
+            .filter(|&pid| cand.keys[pid] != 1.0)
+            .collect();
+
+        let mut budget = start_budget(self.max_starts);
+
+        run_events(spec, horizon, logger, |s| {
+            for &pid in &order {
+                while budget > 0 && s.can_start(spec, pid) {
+                    s.start(spec, pid, record);
+                    budget -= 1;
+                    if !consumes(spec, pid) {
+                        break;
+                    }
+                }
+            }
+        })
+    }
+}
+
+/*
+parallel schedule generation : at every cycle each process that can start is started once,
+in priority order, and passes go on until one starts nothing
+the stocks are shared out between the processes instead of going to the first one
+*/
+pub struct ParallelSgs {
+    pub max_starts: usize,
+}
+
+impl Decoder for ParallelSgs {
+    fn name(&self) -> &'static str {
+        "parallel"
+    }
+
+    fn decode(
+        &self,
+        spec: &SimSpec,
+        cand: &Genome,
+        horizon: i64,
+        record: bool,
+        logger: Option<&mut Logger>,
+    ) -> (i64, Sim) {
+        let order: Vec<usize> = priority_from_keys(&cand.keys)
+            .into_iter()
+            .filter(|&pid| cand.keys[pid] != 1.0)
+            .collect();
+        let mut started = vec![false; cand.keys.len()];
+        let mut budget = start_budget(self.max_starts);
+
+        run_events(spec, horizon, logger, |s| {
+            started.fill(false);
+            loop {
+                let mut any = false;
+                for &pid in &order {
+                    if started[pid] && !consumes(spec, pid) {
+                        continue;
+                    }
+                    if budget > 0 && s.can_start(spec, pid) {
+                        s.start(spec, pid, record);
+                        budget -= 1;
+                        started[pid] = true;
+                        any = true;
+                    }
+                }
+                if !any {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+// the decoder of a run, a GA setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecoderKind {
+    #[default]
+    Heuristic,
+    Serial,
+    Parallel,
+}
+
+impl DecoderKind {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "heuristic" => Ok(DecoderKind::Heuristic),
+            "serial" => Ok(DecoderKind::Serial),
+            "parallel" => Ok(DecoderKind::Parallel),
+            _ => Err(format!(
+                "Invalid decoder {}, expected heuristic, serial or parallel",
+                s
+            )),
+        }
+    }
+
+    // the decoder of this kind, the greedy ones starting at most max_starts jobs a run, 0 for no limit
+    pub fn decoder(self, max_starts: usize) -> Box<dyn Decoder> {
+        match self {
+            DecoderKind::Heuristic => Box::new(Heuristic),
+            DecoderKind::Serial => Box::new(SerialSgs { max_starts }),
+            DecoderKind::Parallel => Box::new(ParallelSgs { max_starts }),
+        }
+    }
+}
+
+impl fmt::Display for DecoderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.decoder(0).name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimSpec, evaluate_priorities, parse};
+
+    fn sim_spec(input: &str) -> SimSpec {
+        SimSpec::from_spec(&parse(input).expect("invalid scenario"))
+    }
+
+    const TWO_TARGETS: &str = "a:1\nb:1\np:(a:1):(c:1):5\nq:(b:1):(d:1):5\noptimize:(c;d)\n";
+
+    #[test]
+    fn heuristic_makes_every_target() {
+        let spec = sim_spec(TWO_TARGETS);
+        for order in [[0, 1], [1, 0]] {
+            let (fitness, sim) = evaluate_priorities(&spec, &Heuristic, &order, 1, 100);
+            assert_eq!(fitness, 2);
+            assert_eq!(sim.stocks, vec![0, 0, 1, 1]);
+        }
+    }
+
+    const SHARED_INPUT: &str = "a:2\np:(a:1):(c:1):5\nq:(a:1):(d:1):5\noptimize:(c;d)\n";
+
+    #[test]
+    fn serial_gives_the_stock_to_the_first_process() {
+        let spec = sim_spec(SHARED_INPUT);
+        let genome = Genome::from_priorities(2, &[0, 1], 1);
+        let (fitness, sim) = SerialSgs { max_starts: 0 }.decode(&spec, &genome, 100, true, None);
+        assert_eq!(fitness, 2);
+        assert_eq!(sim.trace, vec![(0, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn parallel_shares_the_stock_out() {
+        let spec = sim_spec(SHARED_INPUT);
+        let genome = Genome::from_priorities(2, &[0, 1], 1);
+        let (fitness, sim) = ParallelSgs { max_starts: 0 }.decode(&spec, &genome, 100, true, None);
+        assert_eq!(fitness, 2);
+        assert_eq!(sim.trace, vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn disabled_process_never_starts() {
+        let spec = sim_spec(SHARED_INPUT);
+        let genome = Genome::from_priorities(2, &[1], 1);
+        for kind in [
+            DecoderKind::Heuristic,
+            DecoderKind::Serial,
+            DecoderKind::Parallel,
+        ] {
+            let (_, sim) = kind.decoder(0).decode(&spec, &genome, 100, true, None);
+            assert!(sim.trace.iter().all(|&(_, pid)| pid == 1), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn target_named_twice_counts_once() {
+        let spec = sim_spec("a:1\np:(a:1):(c:1):5\noptimize:(c;c)\n");
+        let (fitness, _) = evaluate_priorities(&spec, &Heuristic, &[0], 1, 100);
+        assert_eq!(fitness, 1);
+    }
+
+    #[test]
+    fn handing_a_tool_back_is_not_producing_it() {
+        // polish only takes the pan and gives it back, it is a sink the cook wants the pan from
+        let spec = sim_spec(
+            "pan:2\nmeat:1\ncook:(meat:1;pan:1):(cooked:1;pan:1):10\npolish:(pan:1):(pan:1):1\noptimize:(cooked)\n",
+        );
+        let (fitness, sim) = evaluate_priorities(&spec, &Heuristic, &[0, 1], 1, 100);
+        assert_eq!(fitness, 1);
+        assert_eq!(sim.trace, vec![(0, 0)]);
+    }
+}
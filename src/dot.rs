@@ -0,0 +1,74 @@
+use crate::analysis::Analysis;
+use crate::{Optimize, Spec};
+
+/*
+graphviz rendering of a spec, stocks are ellipses and processes boxes
+needs go from a stock to a process, results from a process to a stock with the duration of the process
+optimize targets are filled in gold, what can never be reached is red and dashed
+render with `dot -Tsvg graph.dot -o graph.svg`
+*/
+pub fn format_dot(spec: &Spec, analysis: &Analysis) -> String {
+    let targets = match &spec.optimize {
+        Optimize::Quantity(names) | Optimize::Time(names) => names,
+    };
+
+    let mut out = String::from("digraph krpsim {\n    rankdir=LR;\n");
+
+    out += "\n    // stocks\n";
+    for name in &spec.stock_names {
+        let mut attrs = vec![
+            "shape=ellipse".to_string(),
+            format!("label=\"{}\\n{}\"", name, spec.init_stocks[name]),
+        ];
+        let reachable = analysis.available.contains(name);
+        if targets.contains(name) {
+            attrs.push("fillcolor=gold".to_string());
+        } else if !reachable {
+            attrs.push("fillcolor=mistyrose".to_string());
+        }
+        if !reachable {
+            attrs.push("color=red".to_string());
+            attrs.push("style=\"dashed,filled\"".to_string());
+        } else if targets.contains(name) {
+            attrs.push("style=filled".to_string());
+        }
+        out += &format!("    \"s_{}\" [{}];\n", name, attrs.join(", "));
+    }
+
+    out += "\n    // processes\n";
+    for (pid, p) in spec.processes.iter().enumerate() {
+        let mut attrs = vec![
+            "shape=box".to_string(),
+            format!("label=\"{}\\n{} cycles\"", p.name, p.duration),
+        ];
+        if !analysis.fireable[pid] {
+            attrs.push("color=red".to_string());
+            attrs.push("style=dashed".to_string());
+        }
+        out += &format!("    \"p_{}\" [{}];\n", p.name, attrs.join(", "));
+    }
+
+    out += "\n    // needs and results\n";
+    for (pid, p) in spec.processes.iter().enumerate() {
+        let dead = if analysis.fireable[pid] {
+            ""
+        } else {
+            ", color=red, style=dashed"
+        };
+        for n in &p.needs {
+            out += &format!(
+                "    \"s_{}\" -> \"p_{}\" [label=\"{}\"{}];\n",
+                n.name, p.name, n.quantity, dead
+            );
+        }
+        for r in &p.results {
+            out += &format!(
+                "    \"p_{}\" -> \"s_{}\" [label=\"{} after {}\"{}];\n",
+                p.name, r.name, r.quantity, p.duration, dead
+            );
+        }
+    }
+
+    out += "}\n";
+    out
+}
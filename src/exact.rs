@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::config::RunOptions;
+use crate::decoder::{DEFAULT_MAX_GREEDY_STARTS, DecoderKind, consumes};
+use crate::ga::{
+    Genome, MAX_TIME_QUANTITY, Score, Sim, StopReason, fitness, pack_time_fitness, reached,
+    run_events,
+};
+use crate::logger::Logger;
+use crate::progress::Progress;
+use crate::{Optimize, SimSpec};
+
+pub const DEFAULT_MAX_NODES: usize = 1_000_000;
+// states the dominance check remembers, past that the new ones are explored without being recorded
+const MAX_SEEN_STATES: usize = 1_000_000;
+// nodes between two looks at the clock and the interrupt flag
+const CHECK_EVERY: usize = 4096;
+
+/*
+the best schedule the search found, as (start cycle, proc id) job starts
+`bound` is the upper bound of the fitness at the root, None when the relaxation has none
+(a loop multiplying a stock), the schedule is optimal when the search was exhausted whatever the bound
+*/
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub schedule: Vec<(i64, usize)>,
+    pub fitness: i64,
+    pub nodes: usize,
+    pub bound: Option<i64>,
+}
+
+/*
+simulates a schedule, starting each job at its cycle, the same way the decoders do
+jobs that cannot start are skipped, which never happens with the schedules of the solver
+*/
+pub fn replay(
+    spec: &SimSpec,
+    schedule: &[(i64, usize)],
+    horizon: i64,
+    logger: Option<&mut Logger>,
+) -> (i64, Sim) {
+    let mut next = 0;
+    run_events(spec, horizon, logger, |s| {
+        while next < schedule.len() && schedule[next].0 <= s.time {
+            let pid = schedule[next].1;
+            if schedule[next].0 == s.time && s.can_start(spec, pid) {
+                s.start(spec, pid, true);
+            }
+            next += 1;
+        }
+    })
+}
+
+/*
+maximizes c.x under a.x <= b and x >= 0, with b >= 0 so that x = 0 is a first vertex
+plain tableau simplex with Bland's rule, the problems are a row per stock and a column per process
+None when the problem is unbounded
+*/
+fn lp_max(a: &[Vec<f64>], b: &[f64], c: &[f64]) -> Option<(f64, Vec<f64>)> {
+    const EPS: f64 = 1e-9;
+    let (m, n) = (a.len(), c.len());
+    let width = n + m + 1;
+    let mut t: Vec<Vec<f64>> = (0..m)
+        .map(|i| {
+            let mut row = vec![0.0; width];
+            row[..n].copy_from_slice(&a[i]);
+            row[n + i] = 1.0;
+            row[width - 1] = b[i];
+            row
+        })
+        .collect();
+    let mut z = vec![0.0; width];
+    for j in 0..n {
+        z[j] = -c[j];
+    }
+    let mut basis: Vec<usize> = (n..n + m).collect();
+
+    while let Some(col) = (0..width - 1).find(|&j| z[j] < -EPS) {
+        let mut pivot: Option<usize> = None;
+        for i in 0..m {
+            if t[i][col] <= EPS {
+                continue;
+            }
+            let ratio = t[i][width - 1] / t[i][col];
+            pivot = match pivot {
+                Some(p) => {
+                    let best = t[p][width - 1] / t[p][col];
+                    if ratio < best - EPS || (ratio <= best + EPS && basis[i] < basis[p]) {
+                        Some(i)
+                    } else {
+                        Some(p)
+                    }
+                }
+                None => Some(i),
+            };
+        }
+        let row = pivot?;
+
+        let scale = t[row][col];
+        for v in t[row].iter_mut() {
+            *v /= scale;
+        }
+        let pivot_row = t[row].clone();
+        for (i, r) in t.iter_mut().enumerate() {
+            if i != row && r[col].abs() > EPS {
+                let f = r[col];
+                for (v, p) in r.iter_mut().zip(&pivot_row) {
+                    *v -= f * p;
+                }
+            }
+        }
+        let f = z[col];
+        for (v, p) in z.iter_mut().zip(&pivot_row) {
+            *v -= f * p;
+        }
+        basis[row] = col;
+    }
+
+    let mut x = vec![0.0; n];
+    for (i, &var) in basis.iter().enumerate() {
+        if var < n {
+            x[var] = t[i][width - 1];
+        }
+    }
+    Some((z[width - 1], x))
+}
+
+/*
+what the relaxation needs to know about the scenario
+a stock is a resource when no process changes how much of it there is, only holds it while running
+(poele in steak, moi in recre), its units then give a number of cycles that the jobs share
+*/
+struct Relaxation {
+    // net[pid][stock_id], what a job takes minus what it gives back
+    net: Vec<Vec<f64>>,
+    resources: Vec<usize>,
+    /*
+    the longest job holding each resource that may end past the horizon
+    a job ending then only counts for what it gives to the targets, only those are worth starting so late
+    */
+    longest: Vec<i64>,
+    // processes that take nothing, started at most once per cycle
+    free: Vec<usize>,
+    targets: Vec<usize>,
+}
+
+impl Relaxation {
+    fn new(spec: &SimSpec) -> Self {
+        let (n, stocks) = (spec.process_count(), spec.stock_count());
+        let mut net = vec![vec![0.0; stocks]; n];
+        for (pid, row) in net.iter_mut().enumerate() {
+            for &(id, qty) in &spec.needs[pid] {
+                row[id] += qty as f64;
+            }
+            for &(id, qty) in &spec.results[pid] {
+                row[id] -= qty as f64;
+            }
+        }
+
+        let resources: Vec<usize> = (0..stocks)
+            .filter(|&id| {
+                (0..n).all(|pid| net[pid][id] == 0.0)
+                    && spec.needs.iter().flatten().any(|need| need.0 == id)
+            })
+            .collect();
+        let longest = resources
+            .iter()
+            .map(|&id| {
+                (0..n)
+                    .filter(|&pid| {
+                        spec.needs[pid].iter().any(|need| need.0 == id)
+                            && spec.results[pid]
+                                .iter()
+                                .any(|r| r.1 > 0 && spec.target_stock_ids.contains(&r.0))
+                    })
+                    .map(|pid| spec.durations[pid])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        Relaxation {
+            net,
+            resources,
+            longest,
+            free: (0..n).filter(|&pid| !consumes(spec, pid)).collect(),
+            targets: spec.target_stock_ids.clone(),
+        }
+    }
+
+    /*
+    the most the targets can hold at the end, from how many jobs of every process can still start
+    relaxed to real numbers and to any order :
+    - no stock can end negative, what the jobs take is at most what there is and what they give
+    - the jobs holding a resource share its units over the cycles left
+    - a process taking nothing starts at most once per cycle
+    also gives the number of jobs of every process in the relaxed solution
+    */
+    fn quantity_bound(&self, spec: &SimSpec, s: &Sim, horizon: i64) -> (Option<i64>, Vec<f64>) {
+        let n = spec.process_count();
+        let left = (horizon - s.time) as f64;
+        let have = |id: usize| (s.stocks[id] + s.pending[id]) as f64;
+
+        let mut a = vec![];
+        let mut b = vec![];
+        for id in 0..spec.stock_count() {
+            let row: Vec<f64> = (0..n).map(|pid| self.net[pid][id]).collect();
+            if row.iter().any(|&v| v != 0.0) {
+                a.push(row);
+                b.push(have(id));
+            }
+        }
+        for (&id, &longest) in self.resources.iter().zip(&self.longest) {
+            a.push(
+                (0..n)
+                    .map(|pid| {
+                        let held: i64 = spec.needs[pid]
+                            .iter()
+                            .filter(|need| need.0 == id)
+                            .map(|need| need.1)
+                            .sum();
+                        (held * spec.durations[pid]) as f64
+                    })
+                    .collect(),
+            );
+            b.push(have(id) * (left + longest as f64));
+        }
+        for &pid in &self.free {
+            let mut row = vec![0.0; n];
+            row[pid] = 1.0;
+            a.push(row);
+            b.push(left);
+        }
+
+        let c: Vec<f64> = (0..n)
+            .map(|pid| {
+                -self
+                    .targets
+                    .iter()
+                    .map(|&id| self.net[pid][id])
+                    .sum::<f64>()
+            })
+            .collect();
+        let now: f64 = self.targets.iter().map(|&id| have(id)).sum();
+
+        // past what a Time fitness can hold, or what a f64 counts exactly, there is no bound worth the name
+        let cap = match spec.optimize {
+            Optimize::Time(_) => MAX_TIME_QUANTITY as f64,
+            Optimize::Quantity(_) => (1u64 << f64::MANTISSA_DIGITS) as f64,
+        };
+        match lp_max(&a, &b, &c) {
+            Some((gain, x)) if now + gain < cap => (Some((now + gain + 1e-6).floor() as i64), x),
+            Some((_, x)) => (None, x),
+            None => (None, vec![0.0; n]),
+        }
+    }
+}
+
+/*
+the earliest cycle one of the targets can grow again : every stock is given the earliest cycle
+it can be there at all, whatever the quantity, and a job of a process producing a target
+cannot start before all its needs are there
+*/
+fn earliest_target_gain(spec: &SimSpec, s: &Sim) -> i64 {
+    let mut at: Vec<i64> = s
+        .stocks
+        .iter()
+        .map(|&qty| if qty > 0 { s.time } else { i64::MAX })
+        .collect();
+    for (finish, pid) in s.running_jobs() {
+        for &(id, qty) in &spec.results[pid] {
+            if qty > 0 {
+                at[id] = at[id].min(finish);
+            }
+        }
+    }
+
+    let start = |at: &[i64], pid: usize| {
+        spec.needs[pid]
+            .iter()
+            .filter(|need| need.1 > 0)
+            .map(|need| at[need.0])
+            .max()
+            .unwrap_or(s.time)
+            .max(s.time)
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for pid in 0..spec.process_count() {
+            let begin = start(&at, pid);
+            if begin == i64::MAX {
+                continue;
+            }
+            for &(id, qty) in &spec.results[pid] {
+                if qty > 0 && begin + spec.durations[pid] < at[id] {
+                    at[id] = begin + spec.durations[pid];
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut earliest = i64::MAX;
+    for (finish, pid) in s.running_jobs() {
+        if spec.results[pid]
+            .iter()
+            .any(|r| r.1 > 0 && spec.target_stock_ids.contains(&r.0))
+        {
+            earliest = earliest.min(finish);
+        }
+    }
+    for pid in 0..spec.process_count() {
+        let begin = start(&at, pid);
+        if begin != i64::MAX
+            && spec.results[pid]
+                .iter()
+                .any(|r| r.1 > 0 && spec.target_stock_ids.contains(&r.0))
+        {
+            earliest = earliest.min(begin + spec.durations[pid]);
+        }
+    }
+    earliest
+}
+
+// the cycle and the running jobs of a state, then its stocks and the cycle its targets last grew
+type Running = (i64, Vec<(i64, usize)>);
+type Explored = (Vec<i64>, i64);
+
+// a frame of the depth first search : a state, and the children of it left to try
+struct Frame {
+    sim: Sim,
+    // the processes in the order they are tried this cycle, and how many come before waiting
+    order: Rc<Vec<usize>>,
+    useful: usize,
+    // the jobs of a cycle start in the order of `order`, so that no set of jobs is tried twice
+    min_rank: usize,
+    next: usize,
+    path_len: usize,
+    bound: Option<i64>,
+}
+
+struct Search<'a> {
+    spec: &'a SimSpec,
+    options: &'a RunOptions,
+    progress: &'a Progress,
+    relaxation: Relaxation,
+    t0: Instant,
+    nodes: usize,
+    path: Vec<(i64, usize)>,
+    best: Solution,
+    /*
+    the stocks (and the cycle the targets last grew, for Time) of the states already explored,
+    by cycle and running jobs
+    a state with no more of every stock than one of them cannot do better
+    */
+    seen: HashMap<Running, Vec<Explored>>,
+    seen_states: usize,
+    // the best schedule is still the one seed gave
+    from_seed: bool,
+    stack: Vec<Frame>,
+    stop: Option<StopReason>,
+}
+
+impl<'a> Search<'a> {
+    /*
+    the most a state can end with : its quantity bound, and for Time the earliest cycle it can get there
+    a state that cannot grow its targets anymore keeps at best what it has now
+    */
+    fn bound(&self, s: &Sim) -> (Option<i64>, Vec<f64>) {
+        let horizon = self.options.horizon;
+        let (qty, x) = self.relaxation.quantity_bound(self.spec, s, horizon);
+        let Some(qty) = qty else {
+            return (None, x);
+        };
+        let bound = match self.spec.optimize {
+            Optimize::Quantity(_) => qty,
+            Optimize::Time(_) => {
+                let now: i64 = self
+                    .spec
+                    .target_stock_ids
+                    .iter()
+                    .map(|&id| s.stocks[id])
+                    .sum();
+                let mut bound = fitness(self.spec, s);
+                if qty > now && qty > 0 {
+                    let gain = earliest_target_gain(self.spec, s);
+                    bound = bound.max(pack_time_fitness(qty, gain));
+                }
+                bound
+            }
+        };
+        (Some(bound), x)
+    }
+
+    // true when a state explored before had at least as much of everything, records it otherwise
+    fn dominated(&mut self, s: &Sim) -> bool {
+        let time_matters = matches!(self.spec.optimize, Optimize::Time(_));
+        let last_gain = if time_matters { s.target_last_gain } else { 0 };
+        let front = self.seen.entry((s.time, s.running_jobs())).or_default();
+        let covers = |stocks: &[i64], gain: i64, other: &[i64], other_gain: i64| {
+            gain <= other_gain && stocks.iter().zip(other).all(|(a, b)| a >= b)
+        };
+        if front
+            .iter()
+            .any(|(stocks, gain)| covers(stocks, *gain, &s.stocks, last_gain))
+        {
+            return true;
+        }
+        let len = front.len();
+        front.retain(|(stocks, gain)| !covers(&s.stocks, last_gain, stocks, *gain));
+        self.seen_states -= len - front.len();
+        if self.seen_states < MAX_SEEN_STATES {
+            front.push((s.stocks.clone(), last_gain));
+            self.seen_states += 1;
+        }
+        false
+    }
+
+    /*
+    a first incumbent, so that the bound cuts from the start : the schedules of every decoder
+    for the processes the relaxation runs at the root, most run first, alone then followed by the others
+    */
+    fn seed(&mut self, x: &[f64]) {
+        let (fit, _) = replay(self.spec, &[], self.options.horizon, None);
+        self.best.fitness = fit;
+
+        let mut order: Vec<usize> = (0..x.len()).collect();
+        order.sort_by(|&a, &b| x[b].total_cmp(&x[a]));
+        let useful = x.iter().filter(|&&v| v > 1e-9).count();
+        for kind in [
+            DecoderKind::Heuristic,
+            DecoderKind::Serial,
+            DecoderKind::Parallel,
+        ] {
+            for len in [useful, order.len()] {
+                let genome = Genome::from_priorities(x.len(), &order[..len], 1);
+                let (fit, sim) = kind.decoder(DEFAULT_MAX_GREEDY_STARTS).decode(
+                    self.spec,
+                    &genome,
+                    self.options.horizon,
+                    true,
+                    None,
+                );
+                if fit > self.best.fitness {
+                    self.best.fitness = fit;
+                    self.best.schedule = sim.trace;
+                }
+            }
+        }
+        self.progress.debug(format_args!(
+            "decoders on the relaxed order : best fitness {}",
+            Score::of(self.spec, self.best.fitness)
+        ));
+        self.stop = reached(self.spec, self.options, self.best.fitness);
+    }
+
+    fn leaf(&mut self, s: &Sim) {
+        let fit = fitness(self.spec, s);
+        if fit > self.best.fitness {
+            self.best.fitness = fit;
+            self.best.schedule = self.path.clone();
+            self.from_seed = false;
+            self.progress.debug(format_args!(
+                "node {} : best fitness {} ({:.3}s)",
+                self.nodes,
+                Score::of(self.spec, fit),
+                self.progress.elapsed()
+            ));
+            if self.stop.is_none() {
+                self.stop = reached(self.spec, self.options, fit);
+            }
+        }
+    }
+
+    // a state at the start of a cycle, before any job started at it
+    fn enter(&mut self, s: Sim, moved: bool) {
+        if !moved || s.time >= self.options.horizon {
+            self.leaf(&s);
+            return;
+        }
+        if self.dominated(&s) {
+            return;
+        }
+        let (bound, x) = self.bound(&s);
+        if bound.is_some_and(|bound| bound <= self.best.fitness) {
+            return;
+        }
+
+        // the processes the relaxation runs most come first, waiting comes before the others
+        let mut order: Vec<usize> = (0..self.spec.process_count()).collect();
+        order.sort_by(|&a, &b| x[b].total_cmp(&x[a]));
+        let useful = x.iter().filter(|&&v| v > 1e-9).count();
+        self.push(s, Rc::new(order), useful, 0, bound);
+    }
+
+    fn push(
+        &mut self,
+        sim: Sim,
+        order: Rc<Vec<usize>>,
+        useful: usize,
+        min_rank: usize,
+        bound: Option<i64>,
+    ) {
+        self.nodes += 1;
+        self.stack.push(Frame {
+            sim,
+            order,
+            useful,
+            min_rank,
+            next: 0,
+            path_len: self.path.len(),
+            bound,
+        });
+    }
+
+    fn check_budgets(&mut self) {
+        if self.options.max_nodes != 0 && self.nodes >= self.options.max_nodes {
+            self.stop = Some(StopReason::NodeBudget);
+        } else if self.nodes.is_multiple_of(CHECK_EVERY) {
+            if self
+                .options
+                .time_budget
+                .is_some_and(|budget| self.t0.elapsed().as_secs_f64() >= budget)
+            {
+                self.stop = Some(StopReason::TimeBudget);
+            } else if self
+                .options
+                .interrupt
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::Relaxed))
+            {
+                self.stop = Some(StopReason::Interrupted);
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        let spec = self.spec;
+        let n = spec.process_count();
+        while self.stop.is_none() {
+            let Some(frame) = self.stack.last_mut() else {
+                self.stop = Some(StopReason::Exhausted);
+                break;
+            };
+            // the incumbent may have grown since the frame was pushed
+            if frame.next > n || frame.bound.is_some_and(|bound| bound <= self.best.fitness) {
+                self.stack.pop();
+                continue;
+            }
+            self.path.truncate(frame.path_len);
+
+            // children : the jobs of `order` before `useful`, waiting for the next job to finish, the rest
+            let child = frame.next;
+            frame.next += 1;
+            if child == frame.useful {
+                let mut sim = frame.sim.clone();
+                let moved = sim.advance(spec);
+                self.enter(sim, moved);
+            } else {
+                let rank = if child < frame.useful {
+                    child
+                } else {
+                    child - 1
+                };
+                let pid = frame.order[rank];
+                if rank < frame.min_rank || !frame.sim.can_start(spec, pid) {
+                    continue;
+                }
+                let mut sim = frame.sim.clone();
+                sim.start(spec, pid, false);
+                let min_rank = if consumes(spec, pid) { rank } else { rank + 1 };
+                let (order, useful, bound) = (frame.order.clone(), frame.useful, frame.bound);
+                self.path.push((sim.time, pid));
+                self.push(sim, order, useful, min_rank, bound);
+            }
+            self.check_budgets();
+        }
+    }
+}
+
+/*
+branch and bound over the states of the simulation : the cycle, the stocks and the running jobs
+at every cycle where a job finishes, the search either starts one more job or waits for the next finish
+a branch is cut when the bound of its state cannot beat the best schedule found,
+or when a state with as much of every stock was already explored at the same cycle with the same jobs running
+gives back the best schedule found when the search is exhausted, and the best so far when a budget runs out
+*/
+pub fn solve(spec: &SimSpec, options: &RunOptions, progress: &Progress) -> (Solution, StopReason) {
+    let mut search = Search {
+        spec,
+        options,
+        progress,
+        relaxation: Relaxation::new(spec),
+        t0: Instant::now(),
+        nodes: 0,
+        path: vec![],
+        best: Solution {
+            schedule: vec![],
+            fitness: i64::MIN,
+            nodes: 0,
+            bound: None,
+        },
+        seen: HashMap::new(),
+        seen_states: 0,
+        from_seed: true,
+        stack: vec![],
+        stop: None,
+    };
+
+    let root = Sim::new(spec);
+    let (bound, x) = search.bound(&root);
+    search.best.bound = bound;
+    search.seed(&x);
+    if search.stop.is_none() {
+        search.enter(root, true);
+        search.run();
+    }
+
+    let stop = search.stop.unwrap_or(StopReason::Exhausted);
+    let mut best = search.best;
+    best.nodes = search.nodes;
+    progress.info(format_args!(
+        "explored {} nodes ({:.3}s) : {}, best fitness {}, upper bound {}",
+        best.nodes,
+        progress.elapsed(),
+        stop,
+        Score::of(spec, best.fitness),
+        best.bound
+            .map_or("none".to_string(), |bound| Score::of(spec, bound)
+                .to_string())
+    ));
+    if search.from_seed && stop != StopReason::Exhausted {
+        progress.info(format_args!(
+            "the search found nothing better than its starting schedule, \
+             a larger --max-nodes or the genetic search may do better"
+        ));
+    }
+    (best, stop)
+}
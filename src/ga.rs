@@ -1,40 +1,43 @@
 use std::{
     cmp::{Reverse, min},
-    collections::{BinaryHeap, HashMap, HashSet},
+    collections::{BinaryHeap, HashSet},
+    fmt,
     hash::Hash,
-    i64,
-    sync::Arc,
+    str::FromStr,
+    sync::{Arc, atomic::Ordering},
     time::Instant,
     vec,
 };
 
-use rand::{self, Rng, SeedableRng, rng, rngs::SmallRng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rayon::prelude::*;
 
-use crate::{Job, Optimize, Process, SimSpec, Spec};
-use crate::{Stock, logger::Logger};
-
-const TOP_PCT: f64 = 0.1;
-const BOT_PCT: f64 = 0.2;
-const HEAD_PCT: f64 = 0.5;
-const MAX_POPULATION: usize = 400;
-const MAX_CYCLES: i64 = 10000;
-const DEBUG_WRITE_MODE: bool = true;
-const RESET_VALUE_GEN: i64 = 2;
-const RESET_DIVIDER: i64 = 8;
-const MAX_RESET_VALUE: i64 = 20;
-const ISLANDS_COUNT: usize = 8;
-const MAX_POPULATION_PER_ISLAND: usize = MAX_POPULATION / ISLANDS_COUNT;
-const MUT_CHANCE_DISABLE_PROCESS: f64 = 0.05;
-const MUT_CHANCE_ENABLE_PROCESS: f64 = 0.05;
-const MUT_CHANCE_SWAP_PROCESS: f64 = 0.10;
+use crate::cache::FitnessCache;
+use crate::checkpoint;
+use crate::config::{GaConfig, RunOptions};
+use crate::decoder::Decoder;
+use crate::logger::Logger;
+use crate::memetic;
+use crate::progress::{Level, Progress};
+use crate::spec::Job;
+use crate::{Optimize, SimSpec};
+
+pub const MAX_CYCLES: i64 = 10000;
+pub const DEFAULT_GENERATIONS: usize = 100;
+// bits given to the target quantity in a Time fitness, how soon the targets stopped growing goes above
+const QUANTITY_BITS: i64 = 32;
+// the latest cycle a Time fitness tells apart
+const MAX_TIME_CYCLE: i64 = (1 << 30) - 1;
+// the most target quantity a Time fitness holds
+pub(crate) const MAX_TIME_QUANTITY: i64 = (1 << QUANTITY_BITS) - 1;
 
 #[derive(Default)]
 pub struct Population {
-    candidates: [Vec<Genome>; ISLANDS_COUNT],
+    pub(crate) candidates: Vec<Vec<Genome>>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Genome {
     pub keys: Vec<f64>,
     pub fitness: i64,
@@ -56,6 +59,23 @@ impl Genome {
             disabled_processes,
         }
     }
+
+    /*
+    builds the genome running the processes in the given order, highest priority first
+    processes missing from the order are disabled
+    */
+    pub fn from_priorities(
+        cnt_processes: usize,
+        order: &[usize],
+        pending_stock_divider: i32,
+    ) -> Self {
+        let mut keys = vec![1.0; cnt_processes];
+        for (rank, &pid) in order.iter().enumerate() {
+            keys[pid] = (rank + 1) as f64 / (order.len() + 1) as f64;
+        }
+        let disabled_processes = order.len() < cnt_processes;
+        Genome::new(keys, 0, pending_stock_divider, disabled_processes)
+    }
 }
 
 impl Hash for Genome {
@@ -80,10 +100,19 @@ impl PartialEq for Genome {
 }
 impl Eq for Genome {}
 
+#[derive(Clone)]
 pub struct Sim {
-    time: i64,
-    stocks: Vec<i64>,
+    pub time: i64,
+    pub stocks: Vec<i64>,
+    // quantities of every stock still being produced by the running jobs
+    pub(crate) pending: Vec<i64>,
     running: BinaryHeap<Reverse<Job>>,
+    // (start cycle, proc id) of every job, only filled when tracing
+    pub trace: Vec<(i64, usize)>,
+    // true when the simulation stopped before the horizon because nothing could run anymore
+    pub halted: bool,
+    // last cycle at which one of the target stocks increased
+    pub target_last_gain: i64,
 }
 
 pub fn priority_from_keys(keys: &[f64]) -> Vec<usize> {
@@ -96,163 +125,271 @@ fn quantize(x: f64, scale: f64) -> i64 {
     (x * scale).round() as i64
 }
 
-fn inputs_available(needs: &Vec<(usize, i64)>, stocks: &Vec<i64>) -> bool {
+pub(crate) fn inputs_available(needs: &[(usize, i64)], stocks: &[i64]) -> bool {
     needs.iter().all(|s| stocks[s.0] >= s.1)
 }
 
-fn deficits_for_higher_priority(
-    order: &[usize],
-    pos: usize,
+pub fn eval_fitness(
     spec: &SimSpec,
-    stocks: &Vec<i64>,
-    deficit: &mut Vec<i64>,
-) {
-    for (_, &hp_idx) in order[pos..=pos].iter().enumerate() {
-        // eprintln!("pidx: {}", pidx);
-        if pos == 0 {
-            for result in &spec.results[hp_idx] {
-                let stock_id = result.0;
-                deficit[stock_id] = i64::MAX;
-            }
-            if let Optimize::Quantity(_) = spec.optimize {
-                for need in &spec.needs[hp_idx] {
-                    let stock_id = need.0;
-                    deficit[stock_id] = i64::MAX;
-                }
-            }
-        }
+    decoder: &dyn Decoder,
+    cand: &mut Genome,
+    horizon: i64,
+) -> (i64, Sim) {
+    let (fit, s) = decoder.decode(spec, cand, horizon, false, None);
+    cand.fitness = fit;
+    (fit, s)
+}
 
-        if inputs_available(&spec.needs[hp_idx], stocks) {
-            continue;
-        }
+// same as eval_fitness but records every job start in Sim::trace
+pub fn eval_fitness_traced(
+    spec: &SimSpec,
+    decoder: &dyn Decoder,
+    cand: &mut Genome,
+    horizon: i64,
+) -> (i64, Sim) {
+    let (fit, s) = decoder.decode(spec, cand, horizon, true, None);
+    cand.fitness = fit;
+    (fit, s)
+}
 
-        for &(stock_id, need_qty) in &spec.needs[hp_idx] {
-            let have = stocks[stock_id];
-            let d = need_qty - have;
-            if d > 0 && deficit[stock_id] != i64::MAX {
-                deficit[stock_id] = deficit[stock_id].saturating_add(d);
-            }
-        }
-    }
+// same as eval_fitness_traced, also writing the state at every event to the logger
+pub fn eval_fitness_logged(
+    spec: &SimSpec,
+    decoder: &dyn Decoder,
+    cand: &mut Genome,
+    horizon: i64,
+    logger: &mut Logger,
+) -> (i64, Sim) {
+    let (fit, s) = decoder.decode(spec, cand, horizon, true, Some(logger));
+    cand.fitness = fit;
+    (fit, s)
 }
 
-pub fn eval_fitness(spec: &SimSpec, cand: &mut Genome, horizon: i64) -> (i64, Sim) {
-    let order = priority_from_keys(&cand.keys);
+impl Sim {
+    pub(crate) fn new(spec: &SimSpec) -> Self {
+        Sim {
+            time: 0,
+            stocks: spec.init_stocks.clone(),
+            pending: vec![0; spec.init_stocks.len()],
+            running: BinaryHeap::new(),
+            trace: vec![],
+            halted: false,
+            target_last_gain: 0,
+        }
+    }
 
-    let mut s = Sim {
-        time: 0,
-        stocks: spec.init_stocks.clone(),
-        running: BinaryHeap::new(),
-    };
+    pub(crate) fn can_start(&self, spec: &SimSpec, pid: usize) -> bool {
+        inputs_available(&spec.needs[pid], &self.stocks)
+    }
 
-    let mut pending: Vec<i64> = vec![0; spec.init_stocks.len()];
-    // let mut logger = Logger::new(&s.stocks, "stock_evolution.csv");
-    let mut deficit = vec![0; spec.init_stocks.len()];
-    while s.time < horizon {
-        // if DEBUG_WRITE_MODE {
-        //     if (logger) {
-        //         logger.log_stocks(s.time, &s.stocks);
-        //     }
-        // }
-        deficit.fill(0);
-        deficits_for_higher_priority(&order, 0, spec, &s.stocks, &mut deficit);
-
-        for (pos, &pid) in order.iter().enumerate() {
-            if pos > 1 {
-                deficits_for_higher_priority(&order, pos - 1, spec, &s.stocks, &mut deficit);
-            }
+    // takes the needs of the process now, its results come in when the job finishes
+    pub(crate) fn start(&mut self, spec: &SimSpec, pid: usize, record: bool) {
+        for n in &spec.needs[pid] {
+            self.stocks[n.0] -= n.1;
+        }
 
-            if cand.keys[pid] == 1.0 {
-                continue;
-            }
+        self.running.push(Reverse(Job {
+            finish_time: self.time + spec.durations[pid],
+            proc_id: pid,
+        }));
 
-            if !inputs_available(&spec.needs[pid], &s.stocks) {
-                continue;
-            }
+        if record {
+            self.trace.push((self.time, pid));
+        }
 
-            let should_run = spec.results[pid].iter().any(|r| {
-                let stock_id = r.0;
-                deficit[stock_id] > (pending[stock_id] / cand.pending_stock_divider as i64)
-            });
+        for r in &spec.results[pid] {
+            let stock_id = r.0;
+            let qty = r.1;
+            self.pending[stock_id] += qty;
+        }
+    }
 
-            // eprintln!("deficit : {:?}", deficit);
+    /*
+    jumps to the next job finish and stores the results of every job ending then
+    false when nothing is running anymore, the simulation is then halted
+    */
+    pub(crate) fn advance(&mut self, spec: &SimSpec) -> bool {
+        let Some(Reverse(top)) = self.running.peek() else {
+            self.halted = true;
+            return false;
+        };
+        let t_next = top.finish_time;
 
-            if !should_run || spec.results[pid].is_empty() {
-                continue;
-            }
+        self.time = t_next;
 
-            for n in &spec.needs[pid] {
-                s.stocks[n.0] -= n.1;
+        while let Some(Reverse(job)) = self.running.peek() {
+            if job.finish_time != t_next {
+                break;
             }
+            let Reverse(job) = self.running.pop().unwrap();
 
-            s.running.push(Reverse(Job {
-                finish_time: s.time + spec.durations[pid],
-                proc_id: pid,
-            }));
-
-            for r in &spec.results[pid] {
+            for r in spec.results[job.proc_id].iter() {
                 let stock_id = r.0;
                 let qty = r.1;
-                pending[stock_id] += qty;
+                self.stocks[stock_id] += qty;
+                self.pending[stock_id] -= qty;
+                if qty > 0 && spec.target_stock_ids.contains(&stock_id) {
+                    self.target_last_gain = t_next;
+                }
             }
         }
+        true
+    }
 
-        if let Some(Reverse(top)) = s.running.peek() {
-            let t_next = top.finish_time;
+    // (finish cycle, proc id) of the running jobs, soonest first
+    pub(crate) fn running_jobs(&self) -> Vec<(i64, usize)> {
+        let mut jobs: Vec<(i64, usize)> = self
+            .running
+            .iter()
+            .map(|Reverse(job)| (job.finish_time, job.proc_id))
+            .collect();
+        jobs.sort_unstable();
+        jobs
+    }
+}
 
-            s.time = t_next;
+/*
+the event loop shared by every decoder, `start_jobs` starts what it wants at the current cycle
+then time jumps to the next job finish, until the horizon or until nothing runs anymore
+*/
+pub(crate) fn run_events(
+    spec: &SimSpec,
+    horizon: i64,
+    mut logger: Option<&mut Logger>,
+    mut start_jobs: impl FnMut(&mut Sim),
+) -> (i64, Sim) {
+    let mut s = Sim::new(spec);
 
-            while let Some(Reverse(job)) = s.running.peek() {
-                if job.finish_time != t_next {
-                    break;
-                }
-                let Reverse(job) = s.running.pop().unwrap();
+    while s.time < horizon {
+        start_jobs(&mut s);
 
-                for r in spec.results[job.proc_id].iter() {
-                    let stock_id = r.0;
-                    let qty = r.1;
-                    s.stocks[stock_id] += qty;
-                    pending[stock_id] -= qty;
-                }
-            }
-        } else {
+        if let Some(logger) = logger.as_deref_mut() {
+            logger.log(s.time, &s.stocks, &s.pending, &s.running);
+        }
+
+        if !s.advance(spec) {
             break;
         }
     }
 
-    // eprintln!("s.stocks : {:?}", s.stocks);
-    // eprintln!("fit : {:?}", s.stocks[spec.target_stock_id]);
+    // the jobs finishing at the horizon were not logged yet
+    if !s.halted
+        && let Some(logger) = logger
+    {
+        logger.log(s.time, &s.stocks, &s.pending, &s.running);
+    }
 
-    let fit = s.stocks[spec.target_stock_id];
-    cand.fitness = fit;
-    (fit, s)
+    (fitness(spec, &s), s)
+}
+
+/*
+the quantity is the sum over every optimize target
+for Time, finishing sooner comes first : the cycle at which a target last grew, the earlier the better,
+then the quantity breaks ties
+a schedule that never makes a target has not finished at all and ranks below every one that does
+*/
+pub(crate) fn fitness(spec: &SimSpec, s: &Sim) -> i64 {
+    let qty: i64 = spec.target_stock_ids.iter().map(|&id| s.stocks[id]).sum();
+    match spec.optimize {
+        Optimize::Quantity(_) => qty,
+        Optimize::Time(_) => pack_time_fitness(qty, s.target_last_gain),
+    }
+}
+
+// a Time fitness, packed so that a larger one is a better schedule, Score reads it back
+pub(crate) fn pack_time_fitness(qty: i64, cycle: i64) -> i64 {
+    if qty <= 0 {
+        return qty;
+    }
+    ((MAX_TIME_CYCLE - cycle.clamp(0, MAX_TIME_CYCLE)) << QUANTITY_BITS)
+        | qty.min(MAX_TIME_QUANTITY)
+}
+
+/// A fitness as it is read: the quantity of the optimize targets and, for a `time` scenario,
+/// the cycle at which they last grew.
+///
+/// A `time` schedule is better when that cycle is earlier, the quantity breaking ties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub quantity: i64,
+    pub cycle: Option<i64>,
+}
+
+impl Score {
+    pub fn of(spec: &SimSpec, fitness: i64) -> Self {
+        match spec.optimize {
+            Optimize::Time(_) if fitness > 0 => Score {
+                quantity: fitness & MAX_TIME_QUANTITY,
+                cycle: Some(MAX_TIME_CYCLE - (fitness >> QUANTITY_BITS)),
+            },
+            _ => Score {
+                quantity: fitness,
+                cycle: None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cycle {
+            Some(cycle) => write!(f, "{} by cycle {}", self.quantity, cycle),
+            None => write!(f, "{}", self.quantity),
+        }
+    }
+}
+
+// `<quantity>` or `<quantity>@<cycle>`, as --target-fitness takes it
+impl FromStr for Score {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |v: &str| v.parse::<i64>().map_err(|_| format!("Invalid score {}", s));
+        match s.split_once('@') {
+            Some((quantity, cycle)) => Ok(Score {
+                quantity: number(quantity)?,
+                cycle: Some(number(cycle)?),
+            }),
+            None => Ok(Score {
+                quantity: number(s)?,
+                cycle: None,
+            }),
+        }
+    }
+}
+
+/*
+ChaCha8 rather than SmallRng because its output is the same on every platform and rand version
+every island gets its own stream for every round (0 for the initial population, then generation + 1)
+so what an island draws never depends on the other islands nor on how rayon schedules the evaluations
+*/
+pub fn island_rng(seed: u64, island: usize, round: usize) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(((island as u64) << 32) | round as u64);
+    rng
 }
 
-fn gen_pending_stock_divider() -> i32 {
-    let dividers = vec![
-        1, 2, 4, 6, 8, 10, 25, 50, 75, 100, 125, 150, 175, 200, 225, 250, 275, 300, 325, 350, 375,
-        400, 425, 450, 475, 500,
-    ];
-    // let dividers = vec![2];
-    dividers[rand::rng().random_range(0..dividers.len())]
+// the values a divider can take, in increasing order
+pub const DIVIDERS: [i32; 26] = [
+    1, 2, 4, 6, 8, 10, 25, 50, 75, 100, 125, 150, 175, 200, 225, 250, 275, 300, 325, 350, 375, 400,
+    425, 450, 475, 500,
+];
+
+fn gen_pending_stock_divider(rng: &mut impl Rng) -> i32 {
+    DIVIDERS[rng.random_range(0..DIVIDERS.len())]
 }
 
-fn gen_random_keys(n: usize) -> Vec<f64> {
+fn gen_random_keys(n: usize, rng: &mut impl Rng) -> Vec<f64> {
     let mut random_keys: Vec<f64> = vec![];
-    let mut rng = SmallRng::from_os_rng();
     for _ in 0..n {
-        // random_keys.push(rng().random::<f64>());
         random_keys.push(rng.random::<f64>());
     }
     random_keys
-    // vec![0.01, 0.02, 0.03, 0.04, 0.05, 0.06, 0.07, 0.08, 0.09, 0.1]
-    // vec![0.1, 0.09, 0.08, 0.07, 0.06, 0.05, 0.04, 0.03, 0.02, 0.01]
 }
 
-pub fn disable_rdm_processes(keys: &mut Vec<f64>) -> bool {
-    let mut r = rng();
+pub fn disable_rdm_processes(keys: &mut [f64], r: &mut impl Rng) -> bool {
     let chance_to_disable = r.random::<f64>();
-    let disabled_processes = if chance_to_disable > 0.5 { true } else { false };
+    let disabled_processes = chance_to_disable > 0.5;
     if !disabled_processes {
         return disabled_processes;
     }
@@ -265,241 +402,564 @@ pub fn disable_rdm_processes(keys: &mut Vec<f64>) -> bool {
     disabled_processes
 }
 
-pub fn gen_random_genome(cnt_processes: usize) -> Genome {
-    let mut random_keys = gen_random_keys(cnt_processes);
-    let disabled_processes = disable_rdm_processes(&mut random_keys);
-    // disable_rdm_processes(&mut random_keys);
-    let divider = gen_pending_stock_divider();
+pub fn gen_random_genome(cnt_processes: usize, rng: &mut impl Rng) -> Genome {
+    let mut random_keys = gen_random_keys(cnt_processes, rng);
+    let disabled_processes = disable_rdm_processes(&mut random_keys, rng);
+    let divider = gen_pending_stock_divider(rng);
 
-    // eprintln!("{:?}", wait_cycles);
     Genome::new(random_keys, 0, divider, disabled_processes)
 }
 
-pub fn gen_initial_pop(cnt_processes: usize) -> Population {
+pub fn gen_initial_pop(cnt_processes: usize, config: &GaConfig, seed: u64) -> Population {
     let mut pop: Population = Default::default();
-    for idx in 0..ISLANDS_COUNT {
-        for _ in 0..MAX_POPULATION_PER_ISLAND {
-            let cand = gen_random_genome(cnt_processes);
-            pop.candidates[idx].push(cand);
-        }
+    for idx in 0..config.islands_count {
+        let mut rng = island_rng(seed, idx, 0);
+        let island = (0..config.population_per_island())
+            .map(|_| gen_random_genome(cnt_processes, &mut rng))
+            .collect();
+        pop.candidates.push(island);
     }
     pop
 }
 
-fn mutate(cand: &mut Genome) {
-    let key1 = rng().random_range(0..cand.keys.len());
-    let key2 = rng().random_range(0..cand.keys.len());
+fn mutate(cand: &mut Genome, rng: &mut impl Rng) {
+    let key1 = rng.random_range(0..cand.keys.len());
+    let key2 = rng.random_range(0..cand.keys.len());
     cand.keys.swap(key1, key2);
 }
 
-fn crossover(p1: &Genome, p2: &Genome) -> Genome {
+/* gives a key of 1.0 to one of the enabled processes, if any is left */
+fn mutate_disable(cand: &mut Genome, rng: &mut impl Rng) {
+    let enabled: Vec<usize> = (0..cand.keys.len())
+        .filter(|&pid| cand.keys[pid] != 1.0)
+        .collect();
+    if enabled.is_empty() {
+        return;
+    }
+    cand.keys[enabled[rng.random_range(0..enabled.len())]] = 1.0;
+    cand.disabled_processes = true;
+}
+
+/* draws a fresh key for one of the disabled processes, if any */
+fn mutate_enable(cand: &mut Genome, rng: &mut impl Rng) {
+    let disabled: Vec<usize> = (0..cand.keys.len())
+        .filter(|&pid| cand.keys[pid] == 1.0)
+        .collect();
+    if disabled.is_empty() {
+        return;
+    }
+    cand.keys[disabled[rng.random_range(0..disabled.len())]] = rng.random::<f64>();
+    cand.disabled_processes = disabled.len() > 1;
+}
+
+fn crossover(p1: &Genome, p2: &Genome, head_pct: f64, rng: &mut impl Rng) -> Genome {
     let mut keys: Vec<f64> = vec![];
-    let divider: i32;
     for (k_n, _) in p1.keys.iter().enumerate() {
-        let r = rng().random::<f64>();
-        if r < HEAD_PCT {
+        let r = rng.random::<f64>();
+        if r < head_pct {
             keys.push(p1.keys[k_n]);
         } else {
             keys.push(p2.keys[k_n]);
         }
     }
 
-    let mut r = rng().random::<f64>();
-    if r < HEAD_PCT {
-        divider = p1.pending_stock_divider;
+    let r = rng.random::<f64>();
+    let divider = if r < head_pct {
+        p1.pending_stock_divider
     } else {
-        divider = p2.pending_stock_divider;
-    }
-
-    r = rng().random::<f64>();
+        p2.pending_stock_divider
+    };
 
     Genome::new(keys, 0, divider, p1.disabled_processes)
 }
 
-fn pick_parents<'a>(sorted: &'a [Genome], elite_cnt: usize) -> (&'a Genome, &'a Genome) {
-    let mut r = rand::rng();
-    let ec = elite_cnt.clamp(1, MAX_POPULATION_PER_ISLAND);
+fn pick_parents<'a>(
+    sorted: &'a [Genome],
+    elite_cnt: usize,
+    config: &GaConfig,
+    r: &mut impl Rng,
+) -> (&'a Genome, &'a Genome) {
+    let pop_per_island = config.population_per_island();
+    let ec = elite_cnt.clamp(1, pop_per_island);
 
     let i_elite = r.random_range(0..ec);
 
-    if ec < MAX_POPULATION_PER_ISLAND {
-        let i_other = r.random_range(ec..MAX_POPULATION_PER_ISLAND);
+    if ec < pop_per_island {
+        let i_other = r.random_range(ec..pop_per_island);
         (&sorted[i_elite], &sorted[i_other])
     } else {
-        let i_other = if MAX_POPULATION_PER_ISLAND > 1 {
-            let mut j = r.random_range(0..MAX_POPULATION_PER_ISLAND);
+        let i_other = if pop_per_island > 1 {
+            let mut j = r.random_range(0..pop_per_island);
             if j == i_elite {
-                j = (j + 1) % MAX_POPULATION_PER_ISLAND;
+                j = (j + 1) % pop_per_island;
             }
             j
         } else {
             0
         };
-        // if rng().random::<f64>() > 0.5 {
-        //     return (&sorted[i_elite], &sorted[i_other]);
-        // } else {
-        //     return (&sorted[i_other], &sorted[i_elite]);
-        // }
         (&sorted[i_elite], &sorted[i_other])
     }
 }
 
-pub fn run_ga(spec: Arc<SimSpec>, mut pop: Population, generations: usize) -> Genome {
-    let mut best_cands: Vec<Genome> = vec![];
-    for idx in 0..ISLANDS_COUNT {
-        pop.candidates[idx]
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(i, cand)| {
-                eval_fitness(&spec, cand, MAX_CYCLES);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Generations,
+    TimeBudget,
+    TargetFitness,
+    Optimum,
+    Stagnation,
+    Interrupted,
+    // exact solver only
+    Exhausted,
+    NodeBudget,
+}
+
+impl StopReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::Generations => "generations",
+            StopReason::TimeBudget => "time_budget",
+            StopReason::TargetFitness => "target_fitness",
+            StopReason::Optimum => "optimum",
+            StopReason::Stagnation => "stagnation",
+            StopReason::Interrupted => "interrupted",
+            StopReason::Exhausted => "exhausted",
+            StopReason::NodeBudget => "node_budget",
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            StopReason::Generations => "generation limit reached",
+            StopReason::TimeBudget => "time budget spent",
+            StopReason::TargetFitness => "target fitness reached",
+            StopReason::Optimum => "known optimum reached",
+            StopReason::Stagnation => "no improvement for too long",
+            StopReason::Interrupted => "interrupted",
+            StopReason::Exhausted => "search space exhausted, optimal",
+            StopReason::NodeBudget => "node budget spent",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// true when `fitness` makes the quantity of `target`, by its cycle if it has one
+fn meets(spec: &SimSpec, fitness: i64, target: Score) -> bool {
+    let score = Score::of(spec, fitness);
+    // a target cycle is met by finishing then or sooner
+    score.quantity >= target.quantity
+        && target
+            .cycle
+            .is_none_or(|cycle| score.cycle.is_some_and(|c| c <= cycle))
+}
+
+// the optimum or the target fitness of `options`, when `fitness` meets one
+pub(crate) fn reached(spec: &SimSpec, options: &RunOptions, fitness: i64) -> Option<StopReason> {
+    if options
+        .optimum
+        .is_some_and(|optimum| meets(spec, fitness, optimum))
+    {
+        Some(StopReason::Optimum)
+    } else if options
+        .target_fitness
+        .is_some_and(|target| meets(spec, fitness, target))
+    {
+        Some(StopReason::TargetFitness)
+    } else {
+        None
+    }
+}
+
+/*
+everything run_ga needs to go on from where it stopped, which is what a checkpoint saves
+the island rngs are derived from the seed, the island and the generation so they need no saving
+*/
+pub struct GaState {
+    // generation being bred
+    pub generation: usize,
+    // next island to breed in that generation
+    pub island: usize,
+    pub pop: Population,
+    pub best_cands: Vec<Genome>,
+    pub last_improvment: Vec<i64>,
+    pub best_fitness: Vec<i64>,
+    pub current_reset_value: Vec<i64>,
+    pub last_wipe_improvments: Vec<bool>,
+    // best fitness over all islands, and generations since it last grew
+    pub overall_best: i64,
+    pub stale_generations: usize,
+}
+
+impl GaState {
+    // evaluates the initial population
+    pub fn new(spec: &SimSpec, mut pop: Population, config: &GaConfig, horizon: i64) -> Self {
+        let islands_count = config.islands_count;
+
+        let decoder = config.decoder();
+        let mut best_cands: Vec<Genome> = vec![];
+        for idx in 0..islands_count {
+            pop.candidates[idx].par_iter_mut().for_each(|cand| {
+                eval_fitness(spec, decoder.as_ref(), cand, horizon);
             });
-        best_cands.push(
-            pop.candidates[idx]
-                .iter()
-                .max_by_key(|c| c.fitness)
-                .unwrap()
-                .clone(),
-        );
+            best_cands.push(
+                pop.candidates[idx]
+                    .iter()
+                    .max_by_key(|c| c.fitness)
+                    .unwrap()
+                    .clone(),
+            );
+        }
+
+        let overall_best = best_cands.iter().map(|c| c.fitness).max().unwrap_or(0);
+        Self {
+            generation: 0,
+            island: 0,
+            pop,
+            best_cands,
+            last_improvment: vec![0; islands_count],
+            best_fitness: vec![0; islands_count],
+            current_reset_value: vec![config.reset_value_gen; islands_count],
+            last_wipe_improvments: vec![false; islands_count],
+            overall_best,
+            stale_generations: 0,
+        }
     }
+}
 
-    let mut last_improvment: Vec<i64> = vec![0; ISLANDS_COUNT];
-    let mut best_fitness: Vec<i64> = vec![0; ISLANDS_COUNT];
-    let mut current_reset_value: Vec<i64> = vec![RESET_VALUE_GEN; ISLANDS_COUNT];
-    let mut last_wipe_improvments: Vec<bool> = vec![false; ISLANDS_COUNT];
+fn save_checkpoint(st: &GaState, options: &RunOptions, config: &GaConfig, progress: &Progress) {
+    let Some(path) = &options.checkpoint else {
+        return;
+    };
+    match checkpoint::save(path, st, options, config) {
+        Ok(()) => progress.debug(format_args!(
+            "checkpoint written to {} at generation {}",
+            path, st.generation
+        )),
+        Err(e) => progress.warn(format_args!("{}", e)),
+    }
+}
 
+/*
+evolves the population until one of the stopping criteria of `options` is met
+the time budget, the fitness targets and an interruption are checked after every island,
+the others after every generation
+a checkpoint is written every `checkpoint_every` generations and when the search stops
+*/
+pub fn run_ga(
+    spec: Arc<SimSpec>,
+    mut st: GaState,
+    config: &GaConfig,
+    options: &RunOptions,
+    progress: &mut Progress,
+) -> (Genome, StopReason) {
+    let islands_count = config.islands_count;
+    let pop_per_island = config.population_per_island();
+    let horizon = options.horizon;
+    let seed = options.seed;
     let t0 = Instant::now();
+    let cache = FitnessCache::new(config.cache_size);
+    let decoder = config.decoder();
+    let decoder = decoder.as_ref();
 
-    for _gen in 0..generations {
-        let now = t0.elapsed().as_secs_f64();
+    let reached = |fitness: i64| reached(&spec, options, fitness);
 
-        eprintln!("now : {:.3}", now);
-        for isl_idx in 0..ISLANDS_COUNT {
-            if isl_idx == ISLANDS_COUNT - 1 {
-                eprintln!(
-                    "Best Genome of generation {} of island {} has {} fitness and divider : {} ",
-                    _gen,
-                    isl_idx,
-                    best_cands[isl_idx].fitness,
-                    best_cands[isl_idx].pending_stock_divider
-                );
-            }
-            // print_genome(&best);
-            pop.candidates[isl_idx].sort_by_key(|g| std::cmp::Reverse(g.fitness));
+    let mut stop = st.best_cands.iter().find_map(|c| reached(c.fitness));
+
+    'search: while stop.is_none() {
+        if options.generations != 0 && st.generation >= options.generations {
+            stop = Some(StopReason::Generations);
+            break;
+        }
+
+        let _gen = st.generation;
+        for isl_idx in st.island..islands_count {
+            let mut rng = island_rng(seed, isl_idx, _gen + 1);
+            st.pop.candidates[isl_idx].sort_by_key(|g| std::cmp::Reverse(g.fitness));
 
-            let elite_cnt = (TOP_PCT * MAX_POPULATION_PER_ISLAND as f64) as usize;
+            let elite_cnt = (config.top_pct * pop_per_island as f64) as usize;
 
-            let bot_cnt = ((BOT_PCT * MAX_POPULATION_PER_ISLAND as f64).round() as usize)
-                .clamp(1, MAX_POPULATION_PER_ISLAND);
+            let bot_cnt = ((config.bot_pct * pop_per_island as f64).round() as usize)
+                .clamp(1, pop_per_island);
 
-            let survivors_end = MAX_POPULATION_PER_ISLAND
-                .saturating_sub(bot_cnt)
-                .max(elite_cnt);
+            let survivors_end = pop_per_island.saturating_sub(bot_cnt).max(elite_cnt);
 
-            let mut next: Vec<Genome> = Vec::with_capacity(MAX_POPULATION_PER_ISLAND as usize);
+            let mut next: Vec<Genome> = Vec::with_capacity(pop_per_island);
 
-            // eprintln!("gen: {}, l: {}", _gen, last_improvment[isl_idx]);
-            if _gen as i64 - last_improvment[isl_idx] > current_reset_value[isl_idx] {
-                // eprintln!("we had to wipe them all but the best one");
-                if last_wipe_improvments[isl_idx] == false {
-                    current_reset_value[isl_idx] = min(
-                        MAX_RESET_VALUE,
-                        current_reset_value[isl_idx]
-                            + _gen as i64 / (MAX_POPULATION_PER_ISLAND as i64 / RESET_DIVIDER),
+            if _gen as i64 - st.last_improvment[isl_idx] > st.current_reset_value[isl_idx] {
+                progress.debug(format_args!(
+                    "generation {} island {} : wiped after {} generations without improvement",
+                    _gen,
+                    isl_idx,
+                    _gen as i64 - st.last_improvment[isl_idx]
+                ));
+                if !st.last_wipe_improvments[isl_idx] {
+                    st.current_reset_value[isl_idx] = min(
+                        config.max_reset_value,
+                        st.current_reset_value[isl_idx]
+                            + _gen as i64 / (pop_per_island as i64 / config.reset_divider),
                     );
-                    // current_reset_value += 2;
                 }
-                last_improvment[isl_idx] = _gen as i64;
-                last_wipe_improvments[isl_idx] = false;
-                next.push(pop.candidates[isl_idx][0].clone());
-                while next.len() < MAX_POPULATION_PER_ISLAND {
-                    next.push(gen_random_genome(spec.needs.len()));
+                st.last_improvment[isl_idx] = _gen as i64;
+                st.last_wipe_improvments[isl_idx] = false;
+                next.push(st.pop.candidates[isl_idx][0].clone());
+                while next.len() < pop_per_island {
+                    next.push(gen_random_genome(spec.needs.len(), &mut rng));
                 }
             } else {
                 // we keep our percentages elites on this island
-                next.extend(pop.candidates[isl_idx].iter().take(elite_cnt).cloned());
+                next.extend(st.pop.candidates[isl_idx].iter().take(elite_cnt).cloned());
 
-                let mut seen: HashSet<Genome> = HashSet::with_capacity(MAX_POPULATION_PER_ISLAND);
+                let mut seen: HashSet<Genome> = HashSet::with_capacity(pop_per_island);
                 for g in &next {
                     seen.insert(g.clone());
                 }
 
-                if isl_idx == ISLANDS_COUNT - 1 {
-                    for idx in 0..ISLANDS_COUNT {
-                        pop.candidates[idx].sort_by_key(|g| std::cmp::Reverse(g.fitness));
-                        let cand = pop.candidates[idx][0].clone();
+                if isl_idx == islands_count - 1 {
+                    for idx in 0..islands_count {
+                        st.pop.candidates[idx].sort_by_key(|g| std::cmp::Reverse(g.fitness));
+                        let cand = st.pop.candidates[idx][0].clone();
                         if seen.insert(cand.clone()) {
                             next.push(cand);
                         }
                     }
                 }
 
-                // eprintln!("sE : {}", survivors_end);
-                // eprintln!("next.len() : {}", next.len());
-                // eprintln!("elite_cnt : {}", elite_cnt);
-
                 while next.len() < survivors_end {
-                    let (p1, p2) = pick_parents(&pop.candidates[isl_idx], elite_cnt);
-                    let mut child = crossover(p1, p2);
-                    if rng().random_bool(MUT_CHANCE_SWAP_PROCESS) {
-                        mutate(&mut child);
+                    let (p1, p2) =
+                        pick_parents(&st.pop.candidates[isl_idx], elite_cnt, config, &mut rng);
+                    let mut child = crossover(p1, p2, config.head_pct, &mut rng);
+                    if rng.random_bool(config.mut_chance_swap_process) {
+                        mutate(&mut child, &mut rng);
+                    }
+                    if rng.random_bool(config.mut_chance_disable_process) {
+                        mutate_disable(&mut child, &mut rng);
+                    }
+                    if rng.random_bool(config.mut_chance_enable_process) {
+                        mutate_enable(&mut child, &mut rng);
                     }
-                    // eprintln!(
-                    // "p1 keys: {:?}, p2 keys: {:?}, child keys: {:?}",
-                    // p1.keys, p2.keys, child.keys
-                    // );
                     next.push(child);
                 }
 
-                while next.len() < MAX_POPULATION_PER_ISLAND {
-                    next.push(gen_random_genome(spec.needs.len()));
+                while next.len() < pop_per_island {
+                    next.push(gen_random_genome(spec.needs.len(), &mut rng));
                 }
             }
 
-            pop.candidates[isl_idx] = next;
+            st.pop.candidates[isl_idx] = next;
+
+            st.pop.candidates[isl_idx].par_iter_mut().for_each(|cand| {
+                cache.fitness(cand, |cand| eval_fitness(&spec, decoder, cand, horizon).0);
+            });
+
+            if config.local_search_evals != 0 {
+                let (improved, tried) = memetic::improve_elites(
+                    &spec,
+                    decoder,
+                    &cache,
+                    horizon,
+                    &mut st.pop.candidates[isl_idx],
+                    elite_cnt.max(1),
+                    config.local_search_evals,
+                );
+                progress.debug(format_args!(
+                    "generation {} island {} : local search improved {} elites with {} evaluations",
+                    _gen, isl_idx, improved, tried
+                ));
+            }
+
+            if let Some(cur_best) = st.pop.candidates[isl_idx].iter().max_by_key(|c| c.fitness)
+                && cur_best.fitness > st.best_cands[isl_idx].fitness
+            {
+                st.best_cands[isl_idx] = cur_best.clone();
+            }
+            if st.best_cands[isl_idx].fitness > st.best_fitness[isl_idx] {
+                st.last_improvment[isl_idx] = _gen as i64;
+                st.best_fitness[isl_idx] = st.best_cands[isl_idx].fitness;
+                st.last_wipe_improvments[isl_idx] = true;
+            }
 
-            // for (index, genome) in pop.candidates.iter_mut().enumerate() {
-            //     let fit = eval_fitness(genome, MAX_CYCLES);
-            //     // eprintln!("Genome {} of generation {_gen} has {}  fitness.", index, fit);
-            // }
+            if progress.wants_islands() {
+                let distinct: HashSet<&Genome> = st.pop.candidates[isl_idx].iter().collect();
+                let diversity = distinct.len() as f64 / st.pop.candidates[isl_idx].len() as f64;
+                progress.island(
+                    _gen,
+                    isl_idx,
+                    Score::of(&spec, st.best_cands[isl_idx].fitness),
+                    diversity,
+                );
+            }
+            if progress.enabled(Level::Trace) {
+                let order: Vec<&str> = priority_from_keys(&st.best_cands[isl_idx].keys)
+                    .into_iter()
+                    .filter(|&pid| st.best_cands[isl_idx].keys[pid] != 1.0)
+                    .map(|pid| spec.process_name(pid))
+                    .collect();
+                progress.trace(format_args!(
+                    "generation {} island {} : best order {}, divider {}",
+                    _gen,
+                    isl_idx,
+                    order.join(" > "),
+                    st.best_cands[isl_idx].pending_stock_divider
+                ));
+            }
 
-            pop.candidates[isl_idx]
-                .par_iter_mut()
-                .enumerate()
-                .for_each(|(i, cand)| {
-                    eval_fitness(&spec, cand, MAX_CYCLES);
-                });
+            st.island = isl_idx + 1;
 
-            if let Some(cur_best) = pop.candidates[isl_idx].iter().max_by_key(|c| c.fitness) {
-                if cur_best.fitness > best_cands[isl_idx].fitness {
-                    best_cands[isl_idx] = cur_best.clone();
-                }
+            stop = reached(st.best_cands[isl_idx].fitness);
+            if stop.is_none()
+                && options
+                    .time_budget
+                    .is_some_and(|budget| t0.elapsed().as_secs_f64() >= budget)
+            {
+                stop = Some(StopReason::TimeBudget);
+            }
+            if stop.is_none()
+                && options
+                    .interrupt
+                    .as_ref()
+                    .is_some_and(|flag| flag.load(Ordering::Relaxed))
+            {
+                stop = Some(StopReason::Interrupted);
+            }
+            if stop.is_some() {
+                break 'search;
             }
-            if best_cands[isl_idx].fitness > best_fitness[isl_idx] {
-                last_improvment[isl_idx] = _gen as i64;
-                best_fitness[isl_idx] = best_cands[isl_idx].fitness;
-                last_wipe_improvments[isl_idx] = true;
-                // if best_cands[isl_idx].fitness > 200_000 {
-                //     return best_cands[isl_idx].clone();
-                // }
+        }
+
+        let best = st.best_cands.iter().map(|c| c.fitness).max().unwrap_or(0);
+        progress.info(format_args!(
+            "generation {} : best fitness {} ({:.3}s)",
+            _gen,
+            Score::of(&spec, best),
+            progress.elapsed()
+        ));
+        if cache.enabled() {
+            let (hits, lookups) = cache.stats();
+            progress.debug(format_args!(
+                "generation {} : fitness cache {} entries, {} hits out of {} evaluations so far",
+                _gen,
+                cache.len(),
+                hits,
+                lookups
+            ));
+        }
+
+        if best > st.overall_best {
+            st.overall_best = best;
+            st.stale_generations = 0;
+        } else {
+            st.stale_generations += 1;
+            if options
+                .stagnation
+                .is_some_and(|n| st.stale_generations >= n)
+            {
+                stop = Some(StopReason::Stagnation);
             }
+        }
+        st.generation += 1;
+        st.island = 0;
 
-            // if isl_idx == ISLANDS_COUNT - 1 {
-            //     let (_, s) = eval_fitness(&mut best_cands[isl_idx], MAX_CYCLES);
-            //     eprintln!("stocks of best_cands[isl_idx] : {:?}", s.stocks);
-            // }
+        if options.checkpoint_every != 0 && st.generation.is_multiple_of(options.checkpoint_every) {
+            save_checkpoint(&st, options, config, progress);
         }
     }
-    best_cands.sort_by_key(|g| std::cmp::Reverse(g.fitness));
 
-    let (f, s) = eval_fitness(&spec, &mut best_cands[0], MAX_CYCLES);
-    // let (f2, s2) = eval_fitness(&best_cands[ISLANDS_COUNT - 1].clone(), MAX_CYCLES);
-    eprintln!(
-        "fitness of best overall is {} and stocks of best overall : {:?}",
-        f, s.stocks
+    // the loop only ends once a reason is set
+    let stop = stop.unwrap_or(StopReason::Generations);
+    // a generation cut short still counts as run
+    progress.stopped(
+        st.generation + (st.island > 0) as usize,
+        stop,
+        cache.stats(),
     );
-    // eprintln!(
-    //     "fitness of best overall is {} and stocks of best overall : {:?}",
-    //     f2, s2.stocks
-    // );
-    best_cands[0].clone()
+    save_checkpoint(&st, options, config, progress);
+
+    let mut best_cands = st.best_cands.clone();
+    best_cands.sort_by_key(|g| std::cmp::Reverse(g.fitness));
+    (best_cands[0].clone(), stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn time_fitness_ranks_the_earlier_finish_first() {
+        let spec = SimSpec::from_spec(&parse("a:1\np:(a:1):(c:1):5\noptimize:(time;c)\n").unwrap());
+        let (early, late) = (pack_time_fitness(2, 20), pack_time_fitness(3, 30));
+        assert!(early > late);
+        assert!(pack_time_fitness(3, 20) > early);
+        assert!(pack_time_fitness(1, MAX_CYCLES) > 0);
+        assert_eq!(
+            Score::of(&spec, early),
+            Score {
+                quantity: 2,
+                cycle: Some(20)
+            }
+        );
+        assert_eq!(Score::of(&spec, early).to_string(), "2 by cycle 20");
+    }
+
+    #[test]
+    fn target_fitness_is_a_quantity_by_a_cycle() {
+        let spec = SimSpec::from_spec(&parse("a:1\np:(a:1):(c:1):5\noptimize:(time;c)\n").unwrap());
+        let options = |target: &str| RunOptions {
+            target_fitness: Some(target.parse().unwrap()),
+            ..RunOptions::default()
+        };
+        let fitness = pack_time_fitness(3, 30);
+        assert_eq!(
+            reached(&spec, &options("3"), fitness),
+            Some(StopReason::TargetFitness)
+        );
+        assert_eq!(
+            reached(&spec, &options("3@30"), fitness),
+            Some(StopReason::TargetFitness)
+        );
+        assert_eq!(reached(&spec, &options("3@29"), fitness), None);
+        assert_eq!(reached(&spec, &options("4"), fitness), None);
+        assert!("3@".parse::<Score>().is_err());
+    }
+
+    #[test]
+    fn disable_and_enable_keep_the_flag_in_step() {
+        let mut rng = island_rng(7, 0, 0);
+        let mut cand = Genome::new(vec![0.2, 1.0, 0.6], 0, 1, true);
+        mutate_enable(&mut cand, &mut rng);
+        assert!(!cand.keys.contains(&1.0));
+        assert!(!cand.disabled_processes);
+        mutate_enable(&mut cand, &mut rng);
+        assert!(!cand.disabled_processes);
+
+        mutate_disable(&mut cand, &mut rng);
+        assert_eq!(cand.keys.iter().filter(|&&k| k == 1.0).count(), 1);
+        assert!(cand.disabled_processes);
+        for _ in 0..3 {
+            mutate_disable(&mut cand, &mut rng);
+        }
+        assert_eq!(cand.keys, vec![1.0; 3]);
+    }
+
+    #[test]
+    fn optimum_of_a_time_scenario_takes_a_cycle() {
+        let spec = SimSpec::from_spec(&parse("a:1\np:(a:1):(c:1):5\noptimize:(time;c)\n").unwrap());
+        let options = |optimum: &str| RunOptions {
+            optimum: Some(optimum.parse().unwrap()),
+            ..RunOptions::default()
+        };
+        let fitness = pack_time_fitness(1, 30);
+        assert_eq!(
+            reached(&spec, &options("1@30"), fitness),
+            Some(StopReason::Optimum)
+        );
+        // the same quantity made later is not the optimum yet
+        assert_eq!(reached(&spec, &options("1@5"), fitness), None);
+        assert_eq!(
+            reached(&spec, &options("1@5"), pack_time_fitness(1, 5)),
+            Some(StopReason::Optimum)
+        );
+    }
 }
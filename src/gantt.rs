@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+
+use crate::Spec;
+use crate::ga::Sim;
+
+const LABEL_WIDTH: f64 = 220.0;
+const PLOT_WIDTH: f64 = 1400.0;
+const LANE_HEIGHT: f64 = 14.0;
+const STRIP_HEIGHT: f64 = 40.0;
+const AXIS_HEIGHT: f64 = 30.0;
+const GAP: f64 = 30.0;
+
+/*
+a job of the schedule, the trace only has the start so the finish comes from the duration
+*/
+struct Bar {
+    pid: usize,
+    start: i64,
+    finish: i64,
+}
+
+/*
+jobs of a process overlapping in time each get their own slot
+a job goes in the first slot free at its start
+*/
+fn slots(bars: &[Bar], process_count: usize) -> Vec<Vec<Vec<&Bar>>> {
+    let mut lanes: Vec<Vec<Vec<&Bar>>> = vec![vec![]; process_count];
+    for bar in bars {
+        let lane = &mut lanes[bar.pid];
+        match lane
+            .iter_mut()
+            .find(|slot| slot.last().is_none_or(|b| b.finish <= bar.start))
+        {
+            Some(slot) => slot.push(bar),
+            None => lane.push(vec![bar]),
+        }
+    }
+    lanes
+}
+
+/*
+replays the trace to get the level of every stock after each cycle where it changed
+needs leave at the start of a job, results come in at its finish
+*/
+fn stock_levels(spec: &Spec, bars: &[Bar]) -> Vec<(String, Vec<(i64, i64)>)> {
+    let mut deltas: BTreeMap<&str, BTreeMap<i64, i64>> = BTreeMap::new();
+    for bar in bars {
+        let p = &spec.processes[bar.pid];
+        for n in &p.needs {
+            *deltas
+                .entry(n.name.as_str())
+                .or_default()
+                .entry(bar.start)
+                .or_insert(0) -= n.quantity;
+        }
+        for r in &p.results {
+            *deltas
+                .entry(r.name.as_str())
+                .or_default()
+                .entry(bar.finish)
+                .or_insert(0) += r.quantity;
+        }
+    }
+
+    spec.stock_names
+        .iter()
+        .map(|name| {
+            let init = spec.init_stocks[name];
+            let mut qty = init;
+            let mut levels = vec![(0, init)];
+            for (&time, &delta) in deltas.get(name.as_str()).into_iter().flatten() {
+                qty += delta;
+                levels.push((time, qty));
+            }
+            (name.clone(), levels)
+        })
+        .collect()
+}
+
+// 1, 2 or 5 times a power of ten, so that there are about 10 ticks on the axis
+fn tick_step(end: i64) -> i64 {
+    let rough = (end as f64 / 10.0).max(1.0);
+    let pow = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * pow)
+        .find(|s| *s >= rough)
+        .unwrap_or(10.0 * pow);
+    step as i64
+}
+
+fn color(pid: usize) -> String {
+    format!("hsl({:.0}, 65%, 55%)", (pid as f64 * 137.508) % 360.0)
+}
+
+/*
+the schedule of a simulation as a standalone SVG
+a lane per process, split in as many rows as the process has jobs running at once,
+then a strip per stock showing its level over time, each scaled to its own peak
+hovering a bar or a strip shows its details
+*/
+pub fn render_svg(spec: &Spec, sim: &Sim) -> String {
+    let bars: Vec<Bar> = sim
+        .trace
+        .iter()
+        .map(|&(start, pid)| Bar {
+            pid,
+            start,
+            finish: start + spec.processes[pid].duration,
+        })
+        .collect();
+
+    let end = bars
+        .iter()
+        .map(|b| b.finish)
+        .max()
+        .unwrap_or(0)
+        .max(sim.time)
+        .max(1);
+    let x = |t: i64| LABEL_WIDTH + t as f64 * PLOT_WIDTH / end as f64;
+
+    let lanes = slots(&bars, spec.processes.len());
+    let levels = stock_levels(spec, &bars);
+
+    let lanes_height: f64 = lanes
+        .iter()
+        .map(|slots| slots.len().max(1) as f64 * LANE_HEIGHT)
+        .sum();
+    let strips_top = AXIS_HEIGHT + lanes_height + GAP;
+    let height = strips_top + levels.len() as f64 * STRIP_HEIGHT + 10.0;
+    let width = LABEL_WIDTH + PLOT_WIDTH + 20.0;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" font-family=\"monospace\" font-size=\"11\">\n",
+        width, height
+    );
+    out += "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n";
+
+    // time axis
+    let step = tick_step(end);
+    let mut t = 0;
+    while t <= end {
+        out += &format!(
+            "<line x1=\"{0:.1}\" y1=\"{1:.1}\" x2=\"{0:.1}\" y2=\"{2:.1}\" stroke=\"#ddd\"/>\n",
+            x(t),
+            AXIS_HEIGHT - 5.0,
+            height
+        );
+        out += &format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+            x(t),
+            AXIS_HEIGHT - 10.0,
+            t
+        );
+        t += step;
+    }
+
+    // one lane per process
+    let mut y = AXIS_HEIGHT;
+    for (pid, slots) in lanes.iter().enumerate() {
+        let lane_height = slots.len().max(1) as f64 * LANE_HEIGHT;
+        if pid % 2 == 1 {
+            out += &format!(
+                "<rect x=\"0\" y=\"{:.1}\" width=\"{:.0}\" height=\"{:.1}\" fill=\"#f4f4f4\"/>\n",
+                y, width, lane_height
+            );
+        }
+        out += &format!(
+            "<text x=\"5\" y=\"{:.1}\">{} ({})</text>\n",
+            y + LANE_HEIGHT - 3.0,
+            spec.processes[pid].name,
+            slots.iter().map(|s| s.len()).sum::<usize>()
+        );
+        for (row, slot) in slots.iter().enumerate() {
+            let top = y + row as f64 * LANE_HEIGHT + 1.0;
+            for bar in slot {
+                out += &format!(
+                    "<rect x=\"{:.2}\" y=\"{:.1}\" width=\"{:.2}\" height=\"{:.1}\" fill=\"{}\"><title>{} : {} -> {}</title></rect>\n",
+                    x(bar.start),
+                    top,
+                    (x(bar.finish) - x(bar.start)).max(0.5),
+                    LANE_HEIGHT - 2.0,
+                    color(pid),
+                    spec.processes[pid].name,
+                    bar.start,
+                    bar.finish
+                );
+            }
+        }
+        y += lane_height;
+    }
+
+    // one strip per stock
+    for (i, (name, levels)) in levels.iter().enumerate() {
+        let top = strips_top + i as f64 * STRIP_HEIGHT;
+        let bottom = top + STRIP_HEIGHT - 4.0;
+        let peak = levels.iter().map(|l| l.1).max().unwrap_or(0).max(1);
+        let y = |qty: i64| bottom - qty.max(0) as f64 * (STRIP_HEIGHT - 8.0) / peak as f64;
+
+        let mut points = format!("{:.2},{:.1}", x(0), bottom);
+        let mut last = 0;
+        for &(time, qty) in levels {
+            points += &format!(" {:.2},{:.1}", x(time), y(last));
+            points += &format!(" {:.2},{:.1}", x(time), y(qty));
+            last = qty;
+        }
+        points += &format!(" {:.2},{:.1} {:.2},{:.1}", x(end), y(last), x(end), bottom);
+
+        out += &format!(
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#bbb\"/>\n",
+            LABEL_WIDTH,
+            bottom,
+            x(end),
+            bottom
+        );
+        out += &format!(
+            "<text x=\"5\" y=\"{:.1}\">{} (max {})</text>\n",
+            bottom - 4.0,
+            name,
+            peak
+        );
+        out += &format!(
+            "<polygon points=\"{}\" fill=\"steelblue\" fill-opacity=\"0.5\" stroke=\"steelblue\"><title>{} : {} at the end, peak {}</title></polygon>\n",
+            points, name, last, peak
+        );
+    }
+
+    out += "</svg>\n";
+    out
+}
+
+// the same chart wrapped in a page, so that it can be opened in a browser
+pub fn render_html(spec: &Spec, sim: &Sim) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>krpsim schedule</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        render_svg(spec, sim)
+    )
+}
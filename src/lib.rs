@@ -0,0 +1,173 @@
+//! Parser, simulator and optimizers for krpsim scenarios.
+//!
+//! A run goes through four steps:
+//! 1. [`parse`] turns the text of a scenario into a [`Spec`] (stocks, processes, optimize targets).
+//! 2. [`SimSpec::from_spec`] flattens it into the id based structure the simulator works on.
+//! 3. [`evaluate_priorities`] simulates one schedule, given as a priority order of processes,
+//!    with one of the [`Decoder`]s.
+//! 4. [`optimize`] searches for the best schedule with the genetic algorithm, or
+//!    [`solve_exact`] with a branch and bound on small scenarios.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use krpsim::{DecoderKind, GaConfig, RunOptions, SimSpec};
+//!
+//! let contents = std::fs::read_to_string("input_files/simple").unwrap();
+//! let spec = krpsim::parse(&contents).expect("invalid scenario");
+//! let sim_spec = Arc::new(SimSpec::from_spec(&spec));
+//!
+//! let options = RunOptions { seed: 42, ..Default::default() };
+//! let mut best = krpsim::optimize(sim_spec.clone(), &GaConfig::default(), &options);
+//!
+//! let decoder = DecoderKind::Heuristic.decoder(0);
+//! let sim = krpsim::simulate(&sim_spec, decoder.as_ref(), &mut best, options.horizon);
+//! print!("{}", krpsim::trace::format_trace(&spec, &sim));
+//! ```
+
+use std::sync::Arc;
+
+pub mod analysis;
+pub mod cache;
+pub mod checkpoint;
+pub mod config;
+pub mod decoder;
+pub mod dot;
+pub mod exact;
+pub mod ga;
+pub mod gantt;
+pub mod logger;
+pub mod memetic;
+pub mod parser;
+pub mod progress;
+pub mod spec;
+pub mod trace;
+pub mod trajectory;
+pub mod verif;
+
+pub use config::{GaConfig, RunOptions};
+pub use decoder::{Decoder, DecoderKind};
+pub use ga::{Genome, Score, Sim, StopReason};
+pub use parser::ParseError;
+pub use progress::{Level, Progress};
+pub use spec::{Optimize, Process, SimSpec, Spec, Stock};
+pub use trajectory::OptimizerKind;
+
+/// Parses the text of a scenario, reporting every error of the file at once.
+///
+/// Each [`ParseError`] can be rendered against `input` with [`ParseError::render`].
+pub fn parse(input: &str) -> Result<Spec, Vec<ParseError>> {
+    parser::parse_spec(input)
+}
+
+/// Simulates the processes of `spec` for `horizon` cycles, started in the given priority order
+/// by `decoder`.
+///
+/// `priorities` holds process ids, highest priority first; processes missing from it never run.
+/// `pending_stock_divider` tunes how much of a stock already being produced still counts as
+/// missing (see [`Genome`]), 1 being the most conservative.
+/// Returns the fitness along with the final state of the simulation and the trace of job starts.
+pub fn evaluate_priorities(
+    spec: &SimSpec,
+    decoder: &dyn Decoder,
+    priorities: &[usize],
+    pending_stock_divider: i32,
+    horizon: i64,
+) -> (i64, Sim) {
+    let mut genome =
+        Genome::from_priorities(spec.process_count(), priorities, pending_stock_divider);
+    ga::eval_fitness_traced(spec, decoder, &mut genome, horizon)
+}
+
+/// Simulates `genome` for `horizon` cycles with `decoder`, recording every job start in
+/// [`Sim::trace`].
+///
+/// The fitness of the genome is updated along the way. The decoder should be the one the genome
+/// was optimized with, [`GaConfig::decoder`].
+pub fn simulate(spec: &SimSpec, decoder: &dyn Decoder, genome: &mut Genome, horizon: i64) -> Sim {
+    ga::eval_fitness_traced(spec, decoder, genome, horizon).1
+}
+
+/// Same as [`simulate`], also writing the stocks, the pending quantities and the running jobs
+/// at every cycle where something happened to `logger`.
+pub fn simulate_logged(
+    spec: &SimSpec,
+    decoder: &dyn Decoder,
+    genome: &mut Genome,
+    horizon: i64,
+    logger: &mut logger::Logger,
+) -> Sim {
+    ga::eval_fitness_logged(spec, decoder, genome, horizon, logger).1
+}
+
+/// Runs the optimizer of `options` on `spec`, the genetic algorithm by default, and returns the
+/// best genome found.
+///
+/// The search stops at the first stopping criterion of `options` met. Two runs with the same
+/// `config` and `options` return the same genome, unless they stop on a time budget.
+/// Nothing is printed, not even a checkpoint that could not be written, see
+/// [`optimize_with_progress`] to follow the run.
+pub fn optimize(spec: Arc<SimSpec>, config: &GaConfig, options: &RunOptions) -> Genome {
+    optimize_with_progress(spec, config, options, &mut Progress::quiet()).0
+}
+
+/// Same as [`optimize`], reporting the progress of the run to `progress` and returning why the
+/// search stopped.
+pub fn optimize_with_progress(
+    spec: Arc<SimSpec>,
+    config: &GaConfig,
+    options: &RunOptions,
+    progress: &mut Progress,
+) -> (Genome, StopReason) {
+    progress.dump_spec(&spec);
+    match options.optimizer {
+        OptimizerKind::Ga => {
+            let pop = ga::gen_initial_pop(spec.process_count(), config, options.seed);
+            let state = ga::GaState::new(&spec, pop, config, options.horizon);
+            ga::run_ga(spec, state, config, options, progress)
+        }
+        OptimizerKind::Annealing => trajectory::anneal(&spec, config, options, progress),
+        OptimizerKind::Tabu => trajectory::tabu(&spec, config, options, progress),
+    }
+}
+
+/// Searches for the best schedule of `spec` by branch and bound, instead of optimizing a
+/// genome.
+///
+/// The search ends with [`StopReason::Exhausted`] when the schedule is proven optimal. It gives
+/// back the best schedule found so far when it runs out of [`RunOptions::max_nodes`], of time or
+/// is interrupted. [`exact::replay`] turns the schedule into a [`Sim`].
+pub fn solve_exact(
+    spec: &SimSpec,
+    options: &RunOptions,
+    progress: &Progress,
+) -> (exact::Solution, StopReason) {
+    progress.dump_spec(spec);
+    exact::solve(spec, options, progress)
+}
+
+/// Goes on with a run saved by [`checkpoint::save`], as if it had never stopped.
+///
+/// The seed, the horizon and the GA settings must be the ones of the checkpoint, which
+/// [`checkpoint::load`] gives back. The generation limit of `options` counts from the start of
+/// the original run.
+pub fn resume(
+    spec: Arc<SimSpec>,
+    checkpoint: checkpoint::Checkpoint,
+    options: &RunOptions,
+    progress: &mut Progress,
+) -> (Genome, StopReason) {
+    progress.dump_spec(&spec);
+    let options = RunOptions {
+        seed: checkpoint.seed,
+        horizon: checkpoint.horizon,
+        ..options.clone()
+    };
+    ga::run_ga(
+        spec,
+        checkpoint.state,
+        &checkpoint.config,
+        &options,
+        progress,
+    )
+}
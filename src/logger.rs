@@ -1,105 +1,252 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, BufWriter, Write};
 
-use crate::ga::{Genome, priority_from_keys};
-use crate::{Optimize, Stock};
+use crate::ga::{Genome, Score, Sim, priority_from_keys};
+use crate::spec::Job;
+use crate::{Optimize, SimSpec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Csv,
+    JsonLines,
+}
+
+impl LogFormat {
+    // JSON Lines for a .jsonl or .json file, CSV otherwise
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".jsonl") || path.ends_with(".json") {
+            LogFormat::JsonLines
+        } else {
+            LogFormat::Csv
+        }
+    }
+}
+
+/*
+writes the state of a simulation at every cycle where something happened:
+stock levels, quantities still being produced and the running jobs
+it is only meant for a single simulation, the GA never logs
+csv : time,<stock>...,pending_<stock>...,running with jobs as process@finish separated by ';'
+jsonl : {"time":..,"stocks":{..},"pending":{..},"running":[{"process":..,"finish":..}]}
+*/
 pub struct Logger {
-    file: File,
-    headers: Vec<String>,
+    out: BufWriter<File>,
+    format: LogFormat,
+    stock_names: Vec<String>,
+    process_names: Vec<String>,
+    // the first write error, the simulation goes on and it is reported by finish
+    error: Option<io::Error>,
 }
 
 impl Logger {
-    pub fn new(stocks: &HashMap<String, i64>, filename: &str) -> Self {
-        let mut file = File::create(filename).unwrap();
-        let headers: Vec<String> = stocks.keys().cloned().collect();
-        let header_line = format!("time,{}", headers.join(","));
-        writeln!(&mut file, "{}", header_line).unwrap();
+    pub fn new(spec: &SimSpec, path: &str, format: LogFormat) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Failed to create the log {} : {}", path, e))?;
+
+        let mut logger = Self {
+            out: BufWriter::new(file),
+            format,
+            stock_names: (0..spec.stock_count())
+                .map(|id| spec.stock_name(id).to_string())
+                .collect(),
+            process_names: (0..spec.process_count())
+                .map(|id| spec.process_name(id).to_string())
+                .collect(),
+            error: None,
+        };
+
+        if format == LogFormat::Csv {
+            let pending: Vec<String> = logger
+                .stock_names
+                .iter()
+                .map(|name| format!("pending_{}", name))
+                .collect();
+            let header = format!(
+                "time,{},{},running",
+                logger.stock_names.join(","),
+                pending.join(",")
+            );
+            logger.write(&header);
+        }
 
-        Self { file, headers }
+        Ok(logger)
     }
 
-    pub fn log_stocks(&mut self, time: i64, stocks: &HashMap<String, i64>) {
-        let row: Vec<String> = self
-            .headers
+    fn write(&mut self, line: &str) {
+        if self.error.is_none()
+            && let Err(e) = writeln!(self.out, "{}", line)
+        {
+            self.error = Some(e);
+        }
+    }
+
+    pub(crate) fn log(
+        &mut self,
+        time: i64,
+        stocks: &[i64],
+        pending: &[i64],
+        running: &BinaryHeap<Reverse<Job>>,
+    ) {
+        let mut jobs: Vec<&Job> = running.iter().map(|Reverse(job)| job).collect();
+        jobs.sort();
+
+        let line = match self.format {
+            LogFormat::Csv => {
+                let jobs: Vec<String> = jobs
+                    .iter()
+                    .map(|j| format!("{}@{}", self.process_names[j.proc_id], j.finish_time))
+                    .collect();
+                format!(
+                    "{},{},{},{}",
+                    time,
+                    join(stocks),
+                    join(pending),
+                    jobs.join(";")
+                )
+            }
+            LogFormat::JsonLines => {
+                let jobs: Vec<String> = jobs
+                    .iter()
+                    .map(|j| {
+                        format!(
+                            "{{\"process\":\"{}\",\"finish\":{}}}",
+                            self.process_names[j.proc_id], j.finish_time
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"time\":{},\"stocks\":{{{}}},\"pending\":{{{}}},\"running\":[{}]}}",
+                    time,
+                    self.json_fields(stocks),
+                    self.json_fields(pending),
+                    jobs.join(",")
+                )
+            }
+        };
+        self.write(&line);
+    }
+
+    // stock names only hold letters and '_', nothing to escape
+    fn json_fields(&self, values: &[i64]) -> String {
+        self.stock_names
             .iter()
-            .map(|k| stocks.get(k).unwrap_or(&0).to_string())
-            .collect();
-        writeln!(self.file, "{},{}", time, row.join(",")).unwrap();
+            .zip(values)
+            .map(|(name, v)| format!("\"{}\":{}", name, v))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        if let Some(e) = self.error.take() {
+            return Err(format!("Failed to write the log : {}", e));
+        }
+        self.out
+            .flush()
+            .map_err(|e| format!("Failed to write the log : {}", e))
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/*
+explains a genome on stderr, so that it does not get mixed with the trace:
+its priority order, the processes it disables, its divider
+and what every process did when the genome was simulated into `sim`
+a job still running at the end of the simulation has fired but produced nothing yet
+*/
+pub fn print_genome(spec: &SimSpec, genome: &Genome, sim: &Sim) {
+    let order = priority_from_keys(&genome.keys);
+
+    let mut fired = vec![0; spec.process_count()];
+    let mut contributed = vec![0; spec.process_count()];
+    for &(start, pid) in &sim.trace {
+        fired[pid] += 1;
+        for &(stock_id, qty) in &spec.needs[pid] {
+            if spec.target_stock_ids.contains(&stock_id) {
+                contributed[pid] -= qty;
+            }
+        }
+        if start + spec.durations[pid] <= sim.time {
+            for &(stock_id, qty) in &spec.results[pid] {
+                if spec.target_stock_ids.contains(&stock_id) {
+                    contributed[pid] += qty;
+                }
+            }
+        }
+    }
+
+    let targets: Vec<&str> = spec
+        .target_stock_ids
+        .iter()
+        .map(|&id| spec.stock_name(id))
+        .collect();
+
+    eprintln!("====================== GENOME ======================");
+    eprintln!(
+        "fitness                 : {}",
+        Score::of(spec, genome.fitness)
+    );
+    eprintln!("pending_stock_divider   : {}", genome.pending_stock_divider);
+    eprintln!("disabled_processes flag : {}", genome.disabled_processes);
+    match &spec.optimize {
+        Optimize::Quantity(_) => {
+            eprintln!("optimize                : Quantity({})", targets.join(";"))
+        }
+        Optimize::Time(_) => eprintln!("optimize                : Time({})", targets.join(";")),
     }
+
+    // disabled processes (keys == 1.0)
+    let disabled: Vec<&str> = order
+        .iter()
+        .filter(|&&pid| genome.keys[pid] == 1.0)
+        .map(|&pid| spec.process_name(pid))
+        .collect();
+    if !disabled.is_empty() {
+        eprintln!("\n--- Disabled processes ------------------------------");
+        eprintln!("  {}", disabled.join(", "));
+    }
+
+    eprintln!("\n--- Processes (priority order: low key = higher prio) -----");
+    eprintln!(
+        "{:<4} {:<24} {:<8} {:<6} {:<6} {:<8} {:<30} {:<30}",
+        "rank", "process", "key", "dur", "fired", "target", "needs", "results"
+    );
+
+    for (rank, &pid) in order.iter().enumerate() {
+        if genome.keys[pid] == 1.0 {
+            continue;
+        }
+        eprintln!(
+            "{:<4} {:<24} {:<8.4} {:<6} {:<6} {:<+8} {:<30} {:<30}",
+            rank,
+            spec.process_name(pid),
+            genome.keys[pid],
+            spec.durations[pid],
+            fired[pid],
+            contributed[pid],
+            fmt_stock_list(spec, &spec.needs[pid]),
+            fmt_stock_list(spec, &spec.results[pid])
+        );
+    }
+
+    eprintln!("====================================================\n");
 }
 
-// pub fn print_genome(genome: &Genome) {
-//     let order = priority_from_keys(&genome.keys);
-//     let spec = &genome;
-
-//     // Header
-//     println!("====================== GENOME ======================");
-//     println!("fitness                 : {}", genome.fitness);
-//     println!("pending_stock_divider   : {}", genome.pending_stock_divider);
-//     println!("disabled_processes flag : {}", genome.disabled_processes);
-
-//     // Optimize target
-//     match &spec.optimize {
-//         Optimize::Quantity(name) => println!("optimize                 : Quantity({})", name),
-//         Optimize::Time(name) => println!("optimize                 : Time({})", name),
-//     }
-
-//     // Initial stocks
-//     // println!("\n--- Initial stocks ---------------------------------");
-//     // let mut init: Vec<_> = spec.init_stocks.iter().collect();
-//     // init.sort_by(|a, b| a.0.cmp(b.0));
-//     // if init.is_empty() {
-//     //     println!("(none)");
-//     // } else {
-//     //     for (name, qty) in init {
-//     //         println!("  {:<16} {}", name, qty);
-//     //     }
-//     // }
-
-//     // Disabled processes (keys == 1.0)
-//     let disabled: Vec<usize> = genome
-//         .keys
-//         .iter()
-//         .enumerate()
-//         .filter_map(|(i, &k)| if k == 1.0 { Some(i) } else { None })
-//         .collect();
-//     if !disabled.is_empty() {
-//         println!("\n--- Disabled processes ------------------------------");
-//         println!("  {:?}", disabled);
-//     }
-
-//     // Table header
-//     println!("\n--- Processes (priority order: low key = higher prio) -----");
-//     println!(
-//         "{:<4} {:<6} {:<12} {:<8} {:<8} {:<40} {:<40}",
-//         "rank", "pid", "key", "wait", "dur", "needs", "results"
-//     );
-
-//     for (rank, &pid) in order.iter().enumerate() {
-//         let p = &spec.processes[pid];
-//         let key = genome.keys[pid];
-//         let needs = fmt_stock_list(&p.needs);
-//         let results = fmt_stock_list(&p.results);
-
-//         println!(
-//             "{:<4} {:<6} {:<12.6}  {:<8} {:<40} {:<40}",
-//             rank, pid, key, p.duration, needs, results
-//         );
-//     }
-
-//     // Also show wait cycles in priority order as a single line (handy for debugging)
-
-//     println!("====================================================\n");
-// }
-
-// // Helper to format a Vec<Stock> like "3 iron, 1 copper"
-// fn fmt_stock_list(v: &[Stock]) -> String {
-//     if v.is_empty() {
-//         return "-".to_string();
-//     }
-//     v.iter()
-//         .map(|s| format!("{} {}", s.quantity, s.name))
-//         .collect::<Vec<_>>()
-//         .join(", ")
-// }
+// formats a list of (stock id, qty) like "3 iron, 1 copper"
+fn fmt_stock_list(spec: &SimSpec, v: &[(usize, i64)]) -> String {
+    if v.is_empty() {
+        return "-".to_string();
+    }
+    v.iter()
+        .map(|&(stock_id, qty)| format!("{} {}", qty, spec.stock_name(stock_id)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
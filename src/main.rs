@@ -1,231 +1,497 @@
-use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::hash::Hash;
-use std::os::unix::process;
 use std::process::exit;
-mod ga;
-mod logger;
-mod parser;
-use std::cmp::Ordering;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use rand::Rng;
 use rand::rng;
-use rayon::iter;
 
-use crate::ga::gen_initial_pop;
-use crate::ga::run_ga;
-
-#[derive(Debug, Clone)]
-pub enum Optimize {
-    Time(String),
-    Quantity(String),
+use krpsim::analysis;
+use krpsim::checkpoint;
+use krpsim::config::DEFAULT_CHECKPOINT_EVERY;
+use krpsim::dot;
+use krpsim::exact::{self, DEFAULT_MAX_NODES};
+use krpsim::ga::{DEFAULT_GENERATIONS, MAX_CYCLES};
+use krpsim::gantt;
+use krpsim::logger::{self, LogFormat, Logger};
+use krpsim::trace::format_trace;
+use krpsim::{
+    GaConfig, Level, Optimize, OptimizerKind, Progress, RunOptions, Score, SimSpec, Spec,
+};
+
+const USAGE: &str = "Usage : KRPSim <input_file> [delay] [options]
+        KRPSim analyze <input_file>
+        KRPSim dot <input_file> [output_file]
+
+  delay, --horizon=<n>    number of cycles simulated for each genome (default 10000)
+  --generations=<n>       number of generations of the genetic algorithm (default 100)
+  --seed=<n>              seed of the run, a random one is drawn when omitted
+  --optimizer=<name>      ga (default), sa for simulated annealing or tabu for tabu search,
+                          sa and tabu get the evaluations of as many GA generations
+  --exact                 searches the schedules themselves by branch and bound instead, proving the
+                          best one optimal when the search space is small enough
+  --max-nodes=<n>         states the exact search may explore before giving up with its best schedule
+                          (default 1000000), 0 removes the limit
+  --time-budget=<secs>    stops the search once it has run that long
+  --target-fitness=<n>    stops the search once a genome makes n of the optimize targets,
+                          <n>@<cycle> also wants them made by that cycle, for a time scenario
+  --optimum=<n>           best known quantity of the optimize targets, stops the search once reached,
+                          <n>@<cycle> for a time scenario, whose optimum is also a cycle
+  --stagnation=<n>        stops the search after n generations without any island improving
+                          the search stops at the first criterion met, --generations=0 removes the limit
+  --checkpoint=<file>     saves the search to the file every few generations and when it stops,
+                          Ctrl-C stops it after the current island, a second Ctrl-C kills it
+  --checkpoint-every=<n>  generations between two checkpoints (default 10), 0 only saves at the end
+  --resume=<file>         goes on with the search saved in the file, with its seed, delay and GA settings,
+                          --generations counts from the start of the saved run
+  --config=<file>         GA settings, one `key = value` per line
+  --log=<file>            writes the stocks, pending quantities and running jobs of the best schedule
+                          at every event, JSON Lines if the file ends in .jsonl or .json, CSV otherwise
+  --verbosity=<level>     quiet, info (default), debug or trace, on stderr
+  --quiet                 same as --verbosity=quiet
+  --progress=<file>       writes one JSON line per island per generation: generation, island,
+                          best_fitness, best_cycle for a time scenario, diversity, elapsed
+  --explain               prints the priority order of the best genome and what each process did, on stderr
+  --gantt=<file>          writes the best schedule as a Gantt chart, HTML if the file ends in .html, SVG otherwise
+  --<ga_setting>=<value>  overrides a GA setting of the config file, e.g. --islands_count=4
+                          top_pct, bot_pct, head_pct, max_population, islands_count,
+                          reset_value_gen, reset_divider, max_reset_value, mut_chance_swap_process,
+                          mut_chance_disable_process, mut_chance_enable_process, cache_size,
+                          decoder (heuristic, serial or parallel), max_greedy_starts, local_search_evals,
+                          sa_temperature, sa_final_temperature, sa_cooling (geometric or linear), tabu_tenure";
+
+struct Args {
+    file_path: String,
+    // the seed is drawn once the arguments are known to be valid
+    options: RunOptions,
+    seed: Option<i64>,
+    // only when given on the command line, a resumed run takes it from the checkpoint
+    horizon: Option<i64>,
+    resume: Option<String>,
+    exact: bool,
+    config: GaConfig,
+    gantt: Option<String>,
+    log: Option<String>,
+    explain: bool,
+    verbosity: Level,
+    progress: Option<String>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Stock {
-    name: String,
-    quantity: i64,
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {} : {}", name, value))
 }
 
-impl Stock {
-    pub fn new(name: &str, quantity: i64) -> Self {
-        Self {
-            name: name.to_string(),
-            quantity,
+/*
+options are accepted both as `--name=value` and `--name value`
+the delay of the classic `krpsim <file> <delay>` interface is the horizon
+*/
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut positional: Vec<&str> = vec![];
+    let mut horizon = None;
+    let mut generations = DEFAULT_GENERATIONS;
+    let mut seed = None;
+    let mut config_path = None;
+    let mut gantt = None;
+    let mut log = None;
+    let mut explain = false;
+    let mut exact = false;
+    let mut max_nodes = DEFAULT_MAX_NODES;
+    let mut verbosity = Level::Info;
+    let mut progress = None;
+    let mut time_budget = None;
+    let mut target_fitness = None;
+    let mut optimum = None;
+    let mut stagnation = None;
+    let mut checkpoint = None;
+    let mut checkpoint_every = DEFAULT_CHECKPOINT_EVERY;
+    let mut resume = None;
+    let mut optimizer = OptimizerKind::Ga;
+    let mut overrides: Vec<(String, String)> = vec![];
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let Some(opt) = arg.strip_prefix("--") else {
+            positional.push(arg);
+            continue;
+        };
+
+        // options without a value
+        match opt {
+            "explain" => {
+                explain = true;
+                continue;
+            }
+            "quiet" => {
+                verbosity = Level::Quiet;
+                continue;
+            }
+            "exact" => {
+                exact = true;
+                continue;
+            }
+            _ => {}
         }
-    }
-}
 
-#[derive(Debug, Clone)]
-pub struct Job {
-    finish_time: i64,
-    proc_id: usize,
-}
+        let (name, value) = match opt.split_once('=') {
+            Some((name, value)) => (name, value.to_string()),
+            None => {
+                let value = it
+                    .next()
+                    .ok_or_else(|| format!("Missing value for --{}", opt))?;
+                (opt, value.clone())
+            }
+        };
 
-impl PartialEq for Job {
-    fn eq(&self, other: &Self) -> bool {
-        self.finish_time == other.finish_time && self.proc_id == other.proc_id
+        match name {
+            "seed" => seed = Some(parse_number("--seed", &value)?),
+            "generations" => generations = parse_number("--generations", &value)?,
+            "horizon" => horizon = Some(parse_number("--horizon", &value)?),
+            "config" => config_path = Some(value),
+            "gantt" => gantt = Some(value),
+            "log" => log = Some(value),
+            "verbosity" => verbosity = Level::parse(&value)?,
+            "progress" => progress = Some(value),
+            "time-budget" | "time_budget" => {
+                time_budget = Some(parse_number("--time-budget", &value)?)
+            }
+            "target-fitness" | "target_fitness" => {
+                target_fitness = Some(parse_number("--target-fitness", &value)?)
+            }
+            "optimum" => optimum = Some(parse_number("--optimum", &value)?),
+            "stagnation" => stagnation = Some(parse_number("--stagnation", &value)?),
+            "checkpoint" => checkpoint = Some(value),
+            "checkpoint-every" | "checkpoint_every" => {
+                checkpoint_every = parse_number("--checkpoint-every", &value)?
+            }
+            "resume" => resume = Some(value),
+            "optimizer" => optimizer = OptimizerKind::parse(&value)?,
+            "max-nodes" | "max_nodes" => max_nodes = parse_number("--max-nodes", &value)?,
+            _ if GaConfig::is_key(name) => overrides.push((name.to_string(), value)),
+            _ => return Err(format!("Unknown option --{}", name)),
+        }
     }
-}
-impl Eq for Job {}
 
-impl PartialOrd for Job {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    let (file_path, delay) = match positional.as_slice() {
+        [file_path] => (file_path.to_string(), None),
+        [file_path, delay] => (
+            file_path.to_string(),
+            Some(parse_number::<i64>("delay", delay)?),
+        ),
+        [] => return Err("Missing input file".to_string()),
+        _ => return Err("Too many arguments".to_string()),
+    };
+
+    if delay.is_some() && horizon.is_some() {
+        return Err("The delay is given twice, as an argument and with --horizon".to_string());
     }
-}
 
-impl Ord for Job {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match self.finish_time.cmp(&other.finish_time) {
-            Ordering::Equal => self.proc_id.cmp(&other.proc_id),
-            ord => ord,
-        }
+    let horizon = delay.or(horizon);
+    if let Some(horizon) = horizon
+        && horizon <= 0
+    {
+        return Err(format!("The delay must be positive : {}", horizon));
     }
-}
 
-#[derive(Default, Debug, Clone)]
-pub struct Process {
-    id: usize,
-    name: String,
-    needs: Vec<Stock>,
-    results: Vec<Stock>,
-    duration: i64,
-}
+    if resume.is_some() && (config_path.is_some() || !overrides.is_empty()) {
+        return Err("A resumed run keeps the GA settings of its checkpoint".to_string());
+    }
 
-impl Process {
-    fn new(id: usize, name: &str, needs: Vec<Stock>, results: Vec<Stock>, duration: i64) -> Self {
-        Self {
-            id,
-            name: name.to_string(),
-            needs,
-            results,
-            duration,
+    if exact {
+        if optimizer != OptimizerKind::Ga {
+            return Err(format!("--exact replaces the {} optimizer", optimizer));
+        }
+        if resume.is_some() || checkpoint.is_some() {
+            return Err("The exact search cannot be checkpointed".to_string());
+        }
+        if explain {
+            return Err("--explain needs a genome, the exact search has none".to_string());
         }
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Spec {
-    processes: Vec<Process>,
-    init_stocks: HashMap<String, i64>,
-    optimize: Optimize,
-}
-
-impl Spec {
-    fn new(processes: Vec<Process>, init_stocks: HashMap<String, i64>, optimize: Optimize) -> Self {
-        Self {
-            processes,
-            init_stocks,
-            optimize,
-        }
+    // the command line wins over the config file
+    let mut config = match config_path {
+        Some(path) => GaConfig::load(&path)?,
+        None => GaConfig::default(),
+    };
+    for (key, value) in &overrides {
+        config.set(key, value)?;
     }
+    config.validate()?;
+
+    let options = RunOptions {
+        generations,
+        horizon: horizon.unwrap_or(MAX_CYCLES),
+        seed: 0,
+        time_budget,
+        target_fitness,
+        optimum,
+        stagnation,
+        // a resumed run keeps saving to the file it came from
+        checkpoint: checkpoint.or(resume.clone()),
+        checkpoint_every,
+        interrupt: None,
+        optimizer,
+        max_nodes,
+    };
+    options.validate()?;
+
+    Ok(Args {
+        file_path,
+        options,
+        seed,
+        horizon,
+        resume,
+        exact,
+        config,
+        gantt,
+        log,
+        explain,
+        verbosity,
+        progress,
+    })
 }
 
-/*
-optimized structure for simulation
-it's a little bit less readable for it is faster
-*/
-#[derive(Debug)]
-pub struct SimSpec {
-    needs: Vec<Vec<(usize, i64)>>, // id/qty
-    results: Vec<Vec<(usize, i64)>>,
-    durations: Vec<i64>,
-    init_stocks: Vec<i64>, // the idx is the id of the stock
-    optimize: Optimize,
-    target_stock_id: usize,
-}
+fn load_spec(file_path: &str) -> Spec {
+    let contents = fs::read_to_string(file_path).unwrap_or_else(|e| {
+        eprintln!("Failed to read the contents of the file : {}", e);
+        exit(1);
+    });
 
-impl SimSpec {
-    fn from_spec(spec: &Spec) -> Self {
+    krpsim::parse(&contents).unwrap_or_else(|errors| {
+        for e in &errors {
+            eprintln!("{}\n", e.render(&contents));
+        }
         eprintln!(
-            "{:?}",
-            spec.processes
-                .iter()
-                .map(|p| p
-                    .needs
-                    .iter()
-                    .map(|s| (&s.name, s.quantity))
-                    .collect::<Vec<_>>())
-                .collect::<Vec<_>>()
+            "Error while parsing the contents of the file : {} error(s)",
+            errors.len()
         );
+        exit(1);
+    })
+}
 
-        let target = match &spec.optimize {
-            Optimize::Quantity(name) | Optimize::Time(name) => name.as_str(),
-        };
+fn finish_progress(progress: Progress) {
+    progress.finish().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(1);
+    });
+}
 
-        let mut target_stock_id = 0;
-
-        /*
-        building this just to get an index/id for each stock, consistent for this scope for needs and results below
-        we dont need the order to stay the same on every program start, which it is not going to be
-        as order in a hashmap is not guaranteed
-        however it will remain consistent for the remainder of the execution of the program
-        as long as we only instanciate this once
-         */
-        let init_stocks_name_to_id: HashMap<String, usize> = spec
-            .init_stocks
-            .iter()
-            .enumerate()
-            .map(|(idx, p)| {
-                if p.0 == target {
-                    target_stock_id = idx;
-                }
-                (p.0.clone(), idx)
-            })
-            .collect();
-
-        let init_stocks: Vec<i64> = spec.init_stocks.iter().map(|s| *s.1).collect();
-
-        let build_vec = |p: &Vec<Stock>| {
-            p.iter()
-                .map(|s| (init_stocks_name_to_id[&s.name], s.quantity))
-                .collect()
-        };
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        std::process::exit(0);
+    }
 
-        let needs: Vec<Vec<(usize, i64)>> =
-            spec.processes.iter().map(|p| build_vec(&p.needs)).collect();
+    if let [command, file_path] = args.as_slice()
+        && command == "analyze"
+    {
+        let spec = load_spec(file_path);
+        print!(
+            "{}",
+            analysis::format_report(&spec, &analysis::analyze(&spec))
+        );
+        return;
+    }
 
-        let results: Vec<Vec<(usize, i64)>> = spec
-            .processes
-            .iter()
-            .map(|p| build_vec(&p.results))
-            .collect();
+    if let [command, file_path, output @ ..] = args.as_slice()
+        && command == "dot"
+        && output.len() <= 1
+    {
+        let spec = load_spec(file_path);
+        let graph = dot::format_dot(&spec, &analysis::analyze(&spec));
+        match output.first() {
+            Some(path) => fs::write(path, graph).unwrap_or_else(|e| {
+                eprintln!("Failed to write {} : {}", path, e);
+                exit(1);
+            }),
+            None => print!("{}", graph),
+        }
+        return;
+    }
 
-        let durations = spec.processes.iter().map(|p| p.duration).collect();
+    let args = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        exit(1);
+    });
 
-        eprintln!("results : {:?}", results);
-        eprintln!("results : {:?}", needs);
+    let spec = load_spec(&args.file_path);
 
-        Self {
-            needs,
-            results,
-            durations,
-            init_stocks,
-            optimize: spec.optimize.clone(),
-            target_stock_id,
-        }
+    let mut progress = Progress::new(args.verbosity);
+    if let Some(path) = &args.progress {
+        progress = progress.with_json(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
     }
-}
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("Usage : cargo run --release -- input_file_path [optional:--seed=<12345>] ");
-        std::process::exit(0);
+    if spec.processes.is_empty() {
+        progress.info(format_args!("No process worth starting!"));
     }
 
-    let file_path = &args[1];
-    let seed: i64 = if args.len() > 2 {
-        args[2].parse().expect("Not a valid seed number")
-    } else {
-        rng().random()
-    };
-
-    let contents = fs::read_to_string(file_path).unwrap_or_else(|e| {
-        eprintln!("Failed to read the contents of the file : {}", e);
+    let sim_spec = Arc::from(SimSpec::from_spec(&spec));
+    for (name, score) in [
+        ("--target-fitness", args.options.target_fitness),
+        ("--optimum", args.options.optimum),
+    ] {
+        if matches!(spec.optimize, Optimize::Quantity(_))
+            && score.is_some_and(|score| score.cycle.is_some())
+        {
+            eprintln!("{} only takes a cycle for a time scenario", name);
+            exit(1);
+        }
+    }
+    if matches!(spec.optimize, Optimize::Time(_))
+        && args
+            .options
+            .optimum
+            .is_some_and(|optimum| optimum.cycle.is_none())
+    {
+        eprintln!("--optimum needs a cycle for a time scenario, e.g. --optimum=3@30");
         exit(1);
-    });
+    }
 
-    let spec = parser::parse_spec(&contents).unwrap_or_else(|e| {
-        eprintln!("Error while parsing the contents of the file : {}", e);
-        exit(1);
+    let checkpoint = args.resume.as_ref().map(|path| {
+        let checkpoint = checkpoint::load(path, &sim_spec).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+        if args.seed.is_some_and(|seed| seed as u64 != checkpoint.seed) {
+            eprintln!(
+                "The checkpoint {} was made with the seed {}",
+                path, checkpoint.seed as i64
+            );
+            exit(1);
+        }
+        if args
+            .horizon
+            .is_some_and(|horizon| horizon != checkpoint.horizon)
+        {
+            eprintln!(
+                "The checkpoint {} was made with the delay {}",
+                path, checkpoint.horizon
+            );
+            exit(1);
+        }
+        checkpoint
     });
 
-    if spec.processes.is_empty() {
-        eprintln!("No process worth starting!");
+    let seed: i64 = match &checkpoint {
+        Some(checkpoint) => checkpoint.seed as i64,
+        None => args.seed.unwrap_or_else(|| rng().random()),
+    };
+
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let flag = interrupt.clone();
+    // the first Ctrl-C lets the search stop cleanly, a second one does not wait
+    let handler = ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::Relaxed) {
+            exit(130);
+        }
+        eprintln!("Interrupted, stopping after the current island");
+    });
+    if let Err(e) = handler {
+        eprintln!("Failed to catch Ctrl-C : {}", e);
     }
 
-    let sim_spec = Arc::from(SimSpec::from_spec(&spec));
+    let options = RunOptions {
+        seed: seed as u64,
+        horizon: checkpoint
+            .as_ref()
+            .map_or(args.options.horizon, |c| c.horizon),
+        interrupt: Some(interrupt),
+        ..args.options
+    };
+    let mut logger = args.log.as_ref().map(|path| {
+        Logger::new(&sim_spec, path, LogFormat::from_path(path)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
 
-    // println!("spec: {:?}", spec);
+    let (sim, best) = if args.exact {
+        let (solution, stop) = krpsim::solve_exact(&sim_spec, &options, &progress);
+        progress.info(format_args!(
+            "Best schedule has fitness {} ({})",
+            Score::of(&sim_spec, solution.fitness),
+            stop
+        ));
+        finish_progress(progress);
+        let (_, sim) = exact::replay(
+            &sim_spec,
+            &solution.schedule,
+            options.horizon,
+            logger.as_mut(),
+        );
+        (sim, None)
+    } else {
+        // the best genome is replayed with the decoder it was optimized with
+        let decoder = checkpoint
+            .as_ref()
+            .map_or(&args.config, |c| &c.config)
+            .decoder();
+        let (mut best, stop) = match checkpoint {
+            Some(checkpoint) => {
+                progress.info(format_args!(
+                    "Resuming at generation {} island {}",
+                    checkpoint.state.generation, checkpoint.state.island
+                ));
+                krpsim::resume(sim_spec.clone(), checkpoint, &options, &mut progress)
+            }
+            None => krpsim::optimize_with_progress(
+                sim_spec.clone(),
+                &args.config,
+                &options,
+                &mut progress,
+            ),
+        };
 
-    let pop = gen_initial_pop(spec.processes.len());
-    let best = run_ga(sim_spec, pop, 100);
+        progress.info(format_args!(
+            "Best genome has fitness {} (seed {}, {})",
+            Score::of(&sim_spec, best.fitness),
+            seed,
+            stop
+        ));
+        finish_progress(progress);
+
+        let sim = match logger.as_mut() {
+            Some(logger) => krpsim::simulate_logged(
+                &sim_spec,
+                decoder.as_ref(),
+                &mut best,
+                options.horizon,
+                logger,
+            ),
+            None => krpsim::simulate(&sim_spec, decoder.as_ref(), &mut best, options.horizon),
+        };
+        (sim, Some(best))
+    };
+    if let Some(logger) = logger {
+        logger.finish().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+    }
+    print!("{}", format_trace(&spec, &sim));
 
-    // println!("sim_spec: {:?}", sim_spec);
+    if args.explain
+        && let Some(best) = &best
+    {
+        logger::print_genome(&sim_spec, best, &sim);
+    }
 
-    eprintln!("Best genome has {} fitness", best.fitness);
+    if let Some(path) = &args.gantt {
+        let chart = if path.ends_with(".html") {
+            gantt::render_html(&spec, &sim)
+        } else {
+            gantt::render_svg(&spec, &sim)
+        };
+        if let Err(e) = fs::write(path, chart) {
+            eprintln!("Failed to write {} : {}", path, e);
+            exit(1);
+        }
+    }
 }
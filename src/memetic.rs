@@ -0,0 +1,116 @@
+use rayon::prelude::*;
+
+use crate::SimSpec;
+use crate::cache::FitnessCache;
+use crate::decoder::Decoder;
+use crate::ga::{DIVIDERS, Genome, eval_fitness, priority_from_keys};
+
+/*
+the tweaks that take a genome to its neighbours, the ones random mutation rarely lands on :
+- two processes next to each other in the priority order swapped
+- a single process disabled (key 1.0), or enabled again right after the last enabled one
+- the divider one step up or down the table of gen_pending_stock_divider
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Swap(usize, usize),
+    Toggle(usize),
+    Divider(i32),
+}
+
+pub fn moves(g: &Genome) -> Vec<Move> {
+    let mut out = vec![];
+
+    let enabled: Vec<usize> = priority_from_keys(&g.keys)
+        .into_iter()
+        .filter(|&pid| g.keys[pid] != 1.0)
+        .collect();
+    for pair in enabled.windows(2) {
+        out.push(Move::Swap(pair[0], pair[1]));
+    }
+
+    out.extend((0..g.keys.len()).map(Move::Toggle));
+
+    if let Some(idx) = DIVIDERS.iter().position(|&d| d == g.pending_stock_divider) {
+        for step in [idx.wrapping_sub(1), idx + 1] {
+            if let Some(&divider) = DIVIDERS.get(step) {
+                out.push(Move::Divider(divider));
+            }
+        }
+    }
+    out
+}
+
+// the neighbour, with no fitness yet
+pub fn apply(g: &Genome, mv: Move) -> Genome {
+    let mut keys = g.keys.clone();
+    let mut divider = g.pending_stock_divider;
+    match mv {
+        Move::Swap(a, b) => keys.swap(a, b),
+        Move::Toggle(pid) if keys[pid] == 1.0 => {
+            let last_key = keys
+                .iter()
+                .copied()
+                .filter(|&k| k != 1.0)
+                .fold(0.0, f64::max);
+            keys[pid] = (last_key + 1.0) / 2.0;
+        }
+        Move::Toggle(pid) => keys[pid] = 1.0,
+        Move::Divider(d) => divider = d,
+    }
+    let disabled = keys.contains(&1.0);
+    Genome::new(keys, 0, divider, disabled)
+}
+
+/*
+hill climbing on the elites of an island, best first
+the whole neighbourhood of an elite is evaluated at once and its best genome replaces the elite
+when it does better, until no neighbour improves or `budget` neighbours were tried
+returns how many elites improved and how many neighbours were tried
+*/
+pub fn improve_elites(
+    spec: &SimSpec,
+    decoder: &dyn Decoder,
+    cache: &FitnessCache,
+    horizon: i64,
+    island: &mut [Genome],
+    elite_cnt: usize,
+    budget: usize,
+) -> (usize, usize) {
+    island.sort_by_key(|g| std::cmp::Reverse(g.fitness));
+
+    let mut tried = 0;
+    let mut improved = 0;
+    for elite in island.iter_mut().take(elite_cnt) {
+        let mut better = false;
+        while tried < budget {
+            let mut candidates: Vec<Genome> = moves(elite)
+                .into_iter()
+                .map(|mv| apply(elite, mv))
+                .collect();
+            candidates.truncate(budget - tried);
+            tried += candidates.len();
+
+            candidates.par_iter_mut().for_each(|cand| {
+                cache.fitness(cand, |cand| eval_fitness(spec, decoder, cand, horizon).0);
+            });
+
+            // the first of the best, so that the result doesn't depend on the threads
+            let best = candidates
+                .into_iter()
+                .reduce(|a, b| if b.fitness > a.fitness { b } else { a });
+            match best {
+                Some(best) if best.fitness > elite.fitness => {
+                    *elite = best;
+                    better = true;
+                }
+                _ => break,
+            }
+        }
+        improved += better as usize;
+        if tried >= budget {
+            break;
+        }
+    }
+    (improved, tried)
+}
@@ -1,189 +1,508 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use crate::Optimize;
 use crate::Process;
 use crate::Spec;
 use crate::Stock;
 
-pub fn parse_spec(input: &str) -> Result<Spec, String> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    InvalidLine,
+    InvalidName,
+    InvalidQuantity,
+    InvalidDelay,
+    MissingBracket,
+    BadOptimize,
+    MultipleOptimize,
+    MissingOptimize,
+    UnknownOptimizeStock,
+}
+
+/*
+line is 1-based, columns are 0-based byte offsets in the line, end excluded
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    // None for errors about the whole file, like a missing optimize line
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, span: Span, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            span: Some(span),
+            message: message.into(),
+        }
+    }
+
+    /*
+    renders the error along with the offending line of the source and a caret under the culprit
+    error: invalid qty `abc` : invalid digit found in string
+     --> line 4, column 7
+      |
+    4 | stone:abc
+      |       ^^^
+    */
+    pub fn render(&self, source: &str) -> String {
+        let mut out = format!("error: {}", self.message);
+        let Some(span) = self.span else {
+            return out;
+        };
+        let Some(line) = source.lines().nth(span.line - 1) else {
+            return out;
+        };
+
+        let nbr = span.line.to_string();
+        let pad = " ".repeat(nbr.len());
+        let caret_start = line[..span.start.min(line.len())].chars().count();
+        let caret_len = line[span.start.min(line.len())..span.end.min(line.len())]
+            .chars()
+            .count()
+            .max(1);
+
+        out += &format!(
+            "\n{}--> line {}, column {}",
+            pad,
+            span.line,
+            caret_start + 1
+        );
+        out += &format!("\n{} |", pad);
+        out += &format!("\n{} | {}", nbr, line);
+        out += &format!(
+            "\n{} | {}{}",
+            pad,
+            " ".repeat(caret_start),
+            "^".repeat(caret_len)
+        );
+        out
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(
+                f,
+                "line {}, column {} : {}",
+                span.line,
+                span.start + 1,
+                self.message
+            ),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/*
+the line being parsed, every &str handed around is a slice of `text`
+so the span of any of them can be found back from its address
+*/
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    nbr: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    fn span(&self, part: &str) -> Span {
+        let start = (part.as_ptr() as usize).saturating_sub(self.text.as_ptr() as usize);
+        let start = start.min(self.text.len());
+        Span {
+            line: self.nbr,
+            start,
+            end: (start + part.len()).min(self.text.len()),
+        }
+    }
+
+    fn whole(&self) -> Span {
+        self.span(self.text)
+    }
+
+    fn err(&self, kind: ParseErrorKind, part: &str, message: impl Into<String>) -> ParseError {
+        ParseError::new(kind, self.span(part), message)
+    }
+}
+
+/*
+every line is parsed even after an error so that all the mistakes of a file are reported at once
+only the first error of a given line is kept
+*/
+pub fn parse_spec(input: &str) -> Result<Spec, Vec<ParseError>> {
     let mut processes: Vec<Process> = vec![];
     let mut stocks: HashMap<String, i64> = Default::default();
-    let mut optimize = None;
-
-    for (line_nbr, line) in input.lines().enumerate() {
-        if line.starts_with('#') || line.is_empty() {
+    // order of first appearance, which gives the stock ids
+    let mut stock_names: Vec<String> = vec![];
+    let mut optimize: Option<(Optimize, Line)> = None;
+    let mut errors: Vec<ParseError> = vec![];
+
+    for (line_idx, text) in input.lines().enumerate() {
+        let line = Line {
+            nbr: line_idx + 1,
+            text,
+        };
+
+        if text.starts_with('#') || text.is_empty() {
             continue;
-        } else if line.starts_with("optimize") {
-            let opt = parse_optimize(line)?;
-            if optimize.replace(opt).is_some() {
-                return Err("Multiples optimize lines".to_string());
+        } else if text.starts_with("optimize") {
+            match parse_optimize(line) {
+                Ok(opt) => {
+                    if optimize.is_some() {
+                        errors.push(ParseError::new(
+                            ParseErrorKind::MultipleOptimize,
+                            line.whole(),
+                            "Multiples optimize lines",
+                        ));
+                    } else {
+                        optimize = Some((opt, line));
+                    }
+                }
+                Err(e) => errors.push(e),
+            }
+        } else if !text.contains('(') {
+            match parse_stock(line, text) {
+                Ok(stock) => {
+                    if !stocks.contains_key(&stock.name) {
+                        stock_names.push(stock.name.clone());
+                    }
+                    stocks.insert(stock.name, stock.quantity);
+                }
+                Err(e) => errors.push(e),
             }
-        } else if !line.contains('(') {
-            let stock = parse_stock(line_nbr, line)?;
-            stocks.insert(stock.name, stock.quantity);
         } else {
-            let process = parse_process(line_nbr, processes.len(), line)?;
-            if !process.results.is_empty() {
-                processes.push(process);
+            match parse_process(line, processes.len()) {
+                Ok(process) => {
+                    // stocks only used by processes start empty
+                    for stock in process.needs.iter().chain(&process.results) {
+                        if !stocks.contains_key(&stock.name) {
+                            stocks.insert(stock.name.clone(), 0);
+                            stock_names.push(stock.name.clone());
+                        }
+                    }
+                    processes.push(process)
+                }
+                Err(e) => errors.push(e),
             }
         }
     }
 
-    for process in &processes {
-        for need in &process.needs {
-            stocks.entry(need.name.clone()).or_insert(0);
-        }
-        for result in &process.results {
-            stocks.entry(result.name.clone()).or_insert(0);
+    match &optimize {
+        None => errors.push(ParseError {
+            kind: ParseErrorKind::MissingOptimize,
+            span: None,
+            message: "Missing optimization".to_string(),
+        }),
+        Some((Optimize::Quantity(names) | Optimize::Time(names), line)) => {
+            for name in names {
+                if !stocks.is_empty() && !stocks.contains_key(name) {
+                    errors.push(ParseError::new(
+                        ParseErrorKind::UnknownOptimizeStock,
+                        optimize_name_span(line, name),
+                        format!("Invalid stock name for optimize : {}", name),
+                    ));
+                }
+            }
         }
     }
 
-    if optimize.is_none() {
-        return Err("Missing optimization".to_string());
+    if !errors.is_empty() {
+        return Err(errors);
     }
 
-    let optimize_stock_name: &str = match optimize.as_ref().unwrap() {
-        Optimize::Quantity(s) | Optimize::Time(s) => s.as_str(),
-    };
-
-    if !stocks.is_empty() && !stocks.contains_key(optimize_stock_name) {
-        return Err("Invalid stock name for optimize".to_string());
-    }
-
-    Ok(Spec::new(processes, stocks, optimize.unwrap()))
+    Ok(Spec::new(
+        processes,
+        stocks,
+        stock_names,
+        optimize.unwrap().0,
+    ))
 }
 
-fn parse_stock(line_nbr: usize, input: &str) -> Result<Stock, String> {
-    let (name, qty_str) = input
-        .split_once(':')
-        .ok_or_else(|| "Invalid line".to_string() + " " + &line_nbr.to_string())?;
+fn parse_stock(line: Line, input: &str) -> Result<Stock, ParseError> {
+    let (name, qty_str) = input.split_once(':').ok_or_else(|| {
+        line.err(
+            ParseErrorKind::InvalidLine,
+            input,
+            "Invalid line, expected name:quantity",
+        )
+    })?;
 
     if qty_str.contains(':') {
-        return Err("Too many \':\' for stock line".to_string());
+        return Err(line.err(
+            ParseErrorKind::InvalidLine,
+            qty_str,
+            "Too many ':' for stock line",
+        ));
     }
 
-    if !name.chars().all(|c| c.is_alphabetic() || c == '_') {
-        return Err("Name of stock must only contain alphabetic characters ".to_string());
+    if name.is_empty() || !name.chars().all(|c| c.is_alphabetic() || c == '_') {
+        return Err(line.err(
+            ParseErrorKind::InvalidName,
+            name,
+            format!(
+                "Invalid stock name `{}`, it must only contain alphabetic characters or '_'",
+                name
+            ),
+        ));
     }
 
-    let qty: i64 = match qty_str.parse::<i64>() {
-        Ok(n) => n,
-        Err(e) => return Err(format!("invalid qty: {}", e)),
-    };
+    let qty: i64 = qty_str.parse::<i64>().map_err(|e| {
+        line.err(
+            ParseErrorKind::InvalidQuantity,
+            qty_str,
+            format!("invalid qty `{}` : {}", qty_str, e),
+        )
+    })?;
 
     Ok(Stock::new(name, qty))
 }
 
-fn parse_process(line_nbr: usize, p_id: usize, input: &str) -> Result<Process, String> {
-    let (name, rest) = input
-        .split_once(':')
-        .ok_or_else(|| "Invalid line".to_string())?;
+fn parse_stock_list(line: Line, input: &str) -> Result<Vec<Stock>, ParseError> {
+    input
+        .split(';')
+        .map(|pair| parse_stock(line, pair))
+        .collect()
+}
 
-    if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        return Err("Name of stock must only contain alphanumeric characters ".to_string());
+fn parse_process(line: Line, p_id: usize) -> Result<Process, ParseError> {
+    let input = line.text;
+    let (name, rest) = input.split_once(':').ok_or_else(|| {
+        line.err(
+            ParseErrorKind::InvalidLine,
+            input,
+            "Invalid line, expected name:(needs):(results):delay",
+        )
+    })?;
+
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(line.err(
+            ParseErrorKind::InvalidName,
+            name,
+            format!(
+                "Invalid process name `{}`, it must only contain alphanumeric characters or '_'",
+                name
+            ),
+        ));
     }
 
     let (rest, delay_str) = rest
         .rsplit_once(':')
-        .ok_or_else(|| "Missing delay".to_string())?;
+        .ok_or_else(|| line.err(ParseErrorKind::InvalidDelay, rest, "Missing delay"))?;
 
-    let delay: i64 = delay_str
-        .parse::<i64>()
-        .map_err(|e| format!("invalid delay: {}", e))?;
+    let delay: i64 = delay_str.parse::<i64>().map_err(|e| {
+        line.err(
+            ParseErrorKind::InvalidDelay,
+            delay_str,
+            format!("invalid delay `{}` : {}", delay_str, e),
+        )
+    })?;
 
     let opening_brackets_cnt = rest.chars().filter(|&c| c == '(').count();
 
     match opening_brackets_cnt {
-        0 | 3.. => return Err("Invalid line".to_string()),
+        0 | 3.. => Err(line.err(
+            ParseErrorKind::InvalidLine,
+            rest,
+            "Invalid line, expected (needs):(results)",
+        )),
         2 => {
-            let (mut needs_str, mut results_str) = rest.split_once(')').ok_or_else(|| {
-                "There must be at least a need or a result for a process".to_string()
+            let (needs_str, results_str) = rest.split_once(')').ok_or_else(|| {
+                line.err(
+                    ParseErrorKind::MissingBracket,
+                    rest,
+                    "Syntax error, missing closing bracket",
+                )
             })?;
 
-            if !needs_str.starts_with('(') {
-                return Err("Syntax error, missing opening bracket".to_string());
-            }
-
-            needs_str = &needs_str[1..needs_str.len()];
-
-            if !results_str.starts_with(":(") {
-                return Err("Syntax error, missing opening bracket or colon".to_string());
-            }
-
-            if !results_str.ends_with(")") {
-                return Err("Syntax error, missing closing bracket".to_string());
+            let Some(needs_str) = needs_str.strip_prefix('(') else {
+                return Err(line.err(
+                    ParseErrorKind::MissingBracket,
+                    needs_str,
+                    "Syntax error, missing opening bracket",
+                ));
+            };
+
+            let Some(results_str) = results_str.strip_prefix(":(") else {
+                return Err(line.err(
+                    ParseErrorKind::MissingBracket,
+                    results_str,
+                    "Syntax error, missing opening bracket or colon",
+                ));
+            };
+
+            let Some(results_str) = results_str.strip_suffix(')') else {
+                return Err(line.err(
+                    ParseErrorKind::MissingBracket,
+                    results_str,
+                    "Syntax error, missing closing bracket",
+                ));
+            };
+
+            let needs = parse_stock_list(line, needs_str)?;
+            let results = parse_stock_list(line, results_str)?;
+
+            Ok(Process::new(p_id, name, needs, results, delay))
+        }
+        1 => {
+            if let Some(needs_str) = rest.strip_prefix('(') {
+                let Some(needs_str) = needs_str.strip_suffix("):") else {
+                    return Err(line.err(
+                        ParseErrorKind::MissingBracket,
+                        needs_str,
+                        "Syntax error, expected (needs): when there are no results",
+                    ));
+                };
+                let needs = parse_stock_list(line, needs_str)?;
+
+                Ok(Process::new(p_id, name, needs, vec![], delay))
+            } else if let Some(results_str) = rest.strip_prefix(':') {
+                let Some(results_str) = results_str
+                    .strip_prefix('(')
+                    .and_then(|r| r.strip_suffix(')'))
+                else {
+                    return Err(line.err(
+                        ParseErrorKind::MissingBracket,
+                        results_str,
+                        "Syntax error, expected :(results) when there are no needs",
+                    ));
+                };
+                let results = parse_stock_list(line, results_str)?;
+
+                Ok(Process::new(p_id, name, vec![], results, delay))
+            } else {
+                Err(line.err(
+                    ParseErrorKind::MissingBracket,
+                    rest,
+                    "Syntax error, missing opening bracket",
+                ))
             }
+        }
+    }
+}
 
-            results_str = &results_str[2..results_str.len() - 1];
-
-            let mut needs: Vec<Stock> = vec![];
+// where `name` sits in the list of an optimize line, not in the optimize keyword before it
+fn optimize_name_span(line: &Line, name: &str) -> Span {
+    line.text
+        .split_once(':')
+        .and_then(|(_, list)| list.strip_prefix('('))
+        .and_then(|list| list.split([';', ')']).find(|n| *n == name))
+        .map_or(line.whole(), |n| line.span(n))
+}
 
-            for pair in needs_str.split(';') {
-                let stock = parse_stock(line_nbr, pair)?;
-                needs.push(stock);
-            }
+fn parse_optimize(line: Line) -> Result<Optimize, ParseError> {
+    let bad = |part: &str| {
+        line.err(
+            ParseErrorKind::BadOptimize,
+            part,
+            "Badly formatted optimize line, expected optimize:([time;]stock1[;stock2...])",
+        )
+    };
 
-            let mut results: Vec<Stock> = vec![];
+    let (_, optimize_str) = line.text.split_once(':').ok_or_else(|| bad(line.text))?;
 
-            for pair in results_str.split(';') {
-                let stock = parse_stock(line_nbr, pair)?;
-                results.push(stock);
-            }
-
-            return Ok(Process::new(p_id, name, needs, results, delay));
-        }
-        1 => {
-            if rest.starts_with("(") {
-                let needs_str = &rest[1..rest.len() - 2];
-                let mut needs: Vec<Stock> = vec![];
-                let results: Vec<Stock> = vec![];
-
-                for pair in needs_str.split(';') {
-                    let stock = parse_stock(line_nbr, pair)?;
-                    needs.push(stock);
-                }
+    let Some(optimize_str) = optimize_str
+        .strip_prefix('(')
+        .and_then(|o| o.strip_suffix(')'))
+    else {
+        return Err(bad(optimize_str));
+    };
 
-                return Ok(Process::new(p_id, name, needs, results, delay));
-            } else if rest.starts_with(":") {
-                let results_str = &rest[2..rest.len() - 1];
-                let needs: Vec<Stock> = vec![];
-                let mut results: Vec<Stock> = vec![];
+    let mut names: Vec<&str> = optimize_str.split(';').collect();
+    let time = names[0] == "time";
+    if time {
+        names.remove(0);
+    }
 
-                for pair in results_str.split(';') {
-                    let stock = parse_stock(line_nbr, pair)?;
-                    results.push(stock);
-                }
+    if names.is_empty() {
+        return Err(bad(optimize_str));
+    }
+    if let Some(n) = names.iter().find(|n| n.is_empty() || **n == "time") {
+        return Err(bad(n));
+    }
 
-                return Ok(Process::new(p_id, name, needs, results, delay));
-            } else {
-                return Err("Invalid line".to_string());
-            }
-        }
+    let names = names.into_iter().map(|n| n.to_string()).collect();
+    if time {
+        Ok(Optimize::Time(names))
+    } else {
+        Ok(Optimize::Quantity(names))
     }
 }
 
-fn parse_optimize(input: &str) -> Result<Optimize, String> {
-    let (_, mut optimize_str) = input
-        .split_once(':')
-        .ok_or_else(|| "Badly formatted optimize line".to_string())?;
-
-    if !optimize_str.starts_with('(') || !optimize_str.ends_with(')') {
-        return Err("Badly formatted optimize line".to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_BAD_LINES: &str =
+        "a:1\nbad:(a:x):(b:1):5\nc:(a:1):(b:1):zz\nd:(a:1):(b:1):1\noptimize:(b)\n";
+
+    #[test]
+    fn bad_quantity_points_at_the_quantity() {
+        let errors = parse_spec(TWO_BAD_LINES).unwrap_err();
+        let e = &errors[0];
+        assert_eq!(e.kind, ParseErrorKind::InvalidQuantity);
+        assert_eq!(
+            e.span,
+            Some(Span {
+                line: 2,
+                start: 7,
+                end: 8
+            })
+        );
     }
 
-    optimize_str = &optimize_str[1..optimize_str.len() - 1];
+    #[test]
+    fn render_puts_a_caret_under_the_span() {
+        let errors = parse_spec(TWO_BAD_LINES).unwrap_err();
+        let rendered = errors[1].render(TWO_BAD_LINES);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[1], " --> line 3, column 15");
+        assert_eq!(lines[3], "3 | c:(a:1):(b:1):zz");
+        assert_eq!(lines[4], "  |               ^^");
+    }
 
-    if optimize_str.chars().filter(|&c| c == ';').count() > 1 {
-        return Err("Badly formatted optimize line".to_string());
+    #[test]
+    fn every_bad_line_is_reported() {
+        let errors = parse_spec(TWO_BAD_LINES).unwrap_err();
+        let kinds: Vec<ParseErrorKind> = errors.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ParseErrorKind::InvalidQuantity,
+                ParseErrorKind::InvalidDelay
+            ]
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| e.span.unwrap().line)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
     }
 
-    match optimize_str.split_once(';') {
-        None => return Ok(Optimize::Quantity(optimize_str.to_string())),
-        Some((left, right)) => {
-            if left != "time" {
-                return Err("Badly formatted optimize line".to_string());
-            }
-            return Ok(Optimize::Time(right.to_string()));
-        }
-    };
+    #[test]
+    fn unknown_target_points_inside_the_list() {
+        let errors = parse_spec("a:1\np:(a:1):(b:1):1\noptimize:(time;b;mize)\n").unwrap_err();
+        assert_eq!(errors[0].kind, ParseErrorKind::UnknownOptimizeStock);
+        assert_eq!(
+            errors[0].span,
+            Some(Span {
+                line: 3,
+                start: 17,
+                end: 21
+            })
+        );
+
+        let errors = parse_spec("a:1\np:(a:1):(b:1):1\noptimize:(tim)\n").unwrap_err();
+        assert_eq!(errors[0].span.unwrap().start, 10);
+    }
 }
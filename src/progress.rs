@@ -0,0 +1,207 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+use crate::SimSpec;
+use crate::ga::{Score, StopReason};
+
+/*
+how much the optimizer tells on stderr, each level also prints everything below it
+quiet : nothing but errors
+info  : the best fitness of every generation, the result and the warnings
+debug : every island of every generation, with its diversity and its wipes
+trace : the processes as the simulator sees them and the priority order of every island best
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Quiet,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "quiet" => Ok(Level::Quiet),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!(
+                "Invalid verbosity {}, expected quiet, info, debug or trace",
+                s
+            )),
+        }
+    }
+}
+
+/*
+progress of a run, as messages on stderr filtered by level
+and optionally as a JSON Lines file with one line per island per generation :
+{"generation":..,"island":..,"best_fitness":..,"diversity":..,"elapsed":..}
+and a last line telling why the run stopped
+best_fitness is the quantity of the optimize targets, a time scenario adds "best_cycle" after it,
+the cycle at which they last grew
+diversity is the share of distinct genomes in the island, elapsed is in seconds
+*/
+pub struct Progress {
+    level: Level,
+    json: Option<BufWriter<File>>,
+    t0: Instant,
+    // the first write error on the json file, reported by finish
+    error: Option<io::Error>,
+}
+
+impl Progress {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            json: None,
+            t0: Instant::now(),
+            error: None,
+        }
+    }
+
+    pub fn quiet() -> Self {
+        Progress::new(Level::Quiet)
+    }
+
+    pub fn with_json(mut self, path: &str) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create the progress file {} : {}", path, e))?;
+        self.json = Some(BufWriter::new(file));
+        Ok(self)
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        level <= self.level
+    }
+
+    // true when the stats of every island are wanted, which costs a bit to compute
+    pub fn wants_islands(&self) -> bool {
+        self.enabled(Level::Debug) || self.json.is_some()
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.t0.elapsed().as_secs_f64()
+    }
+
+    fn print(&self, level: Level, args: fmt::Arguments) {
+        if self.enabled(level) {
+            eprintln!("{}", args);
+        }
+    }
+
+    pub fn info(&self, args: fmt::Arguments) {
+        self.print(Level::Info, args);
+    }
+
+    // something that went wrong without stopping the run, like a checkpoint that was not written
+    pub fn warn(&self, args: fmt::Arguments) {
+        self.print(Level::Info, format_args!("warning : {}", args));
+    }
+
+    pub fn debug(&self, args: fmt::Arguments) {
+        self.print(Level::Debug, args);
+    }
+
+    pub fn trace(&self, args: fmt::Arguments) {
+        self.print(Level::Trace, args);
+    }
+
+    pub fn island(&mut self, generation: usize, island: usize, best: Score, diversity: f64) {
+        let elapsed = self.elapsed();
+        self.debug(format_args!(
+            "generation {} island {} : best {}, diversity {:.2}",
+            generation, island, best, diversity
+        ));
+
+        if let Some(json) = &mut self.json
+            && self.error.is_none()
+        {
+            let cycle = best
+                .cycle
+                .map_or(String::new(), |c| format!(",\"best_cycle\":{}", c));
+            let line = format!(
+                "{{\"generation\":{},\"island\":{},\"best_fitness\":{}{},\"diversity\":{:.4},\"elapsed\":{:.3}}}",
+                generation, island, best.quantity, cycle, diversity, elapsed
+            );
+            if let Err(e) = writeln!(json, "{}", line) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /*
+    the last line of the json file,
+    {"generations":..,"stop":..,"elapsed":..,"cache_hits":..,"cache_lookups":..}
+    */
+    pub fn stopped(&mut self, generations: usize, reason: StopReason, cache: (u64, u64)) {
+        let elapsed = self.elapsed();
+        self.info(format_args!(
+            "stopped after {} generations ({:.3}s) : {}",
+            generations, elapsed, reason
+        ));
+        let (hits, lookups) = cache;
+        if lookups != 0 {
+            self.info(format_args!(
+                "fitness cache : {} hits out of {} evaluations ({:.1}%)",
+                hits,
+                lookups,
+                100.0 * hits as f64 / lookups as f64
+            ));
+        }
+
+        if let Some(json) = &mut self.json
+            && self.error.is_none()
+        {
+            let line = format!(
+                "{{\"generations\":{},\"stop\":\"{}\",\"elapsed\":{:.3},\"cache_hits\":{},\"cache_lookups\":{}}}",
+                generations,
+                reason.as_str(),
+                elapsed,
+                hits,
+                lookups
+            );
+            if let Err(e) = writeln!(json, "{}", line) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    // the processes with their needs and results, as ids and names
+    pub fn dump_spec(&self, spec: &SimSpec) {
+        if !self.enabled(Level::Trace) {
+            return;
+        }
+        let fmt = |v: &[(usize, i64)]| {
+            v.iter()
+                .map(|&(id, qty)| format!("{}#{}:{}", spec.stock_name(id), id, qty))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+        for pid in 0..spec.process_count() {
+            self.trace(format_args!(
+                "process {}#{} : needs ({}) results ({}) duration {}",
+                spec.process_name(pid),
+                pid,
+                fmt(&spec.needs[pid]),
+                fmt(&spec.results[pid]),
+                spec.durations[pid]
+            ));
+        }
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        if let Some(e) = self.error.take() {
+            return Err(format!("Failed to write the progress file : {}", e));
+        }
+        match &mut self.json {
+            Some(json) => json
+                .flush()
+                .map_err(|e| format!("Failed to write the progress file : {}", e)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::analysis;
+
+/*
+every target counts the same, the fitness is the sum of their quantities
+*/
+#[derive(Debug, Clone)]
+pub enum Optimize {
+    Time(Vec<String>),
+    Quantity(Vec<String>),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Stock {
+    pub name: String,
+    pub quantity: i64,
+}
+
+impl Stock {
+    pub fn new(name: &str, quantity: i64) -> Self {
+        Self {
+            name: name.to_string(),
+            quantity,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub(crate) finish_time: i64,
+    pub(crate) proc_id: usize,
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.finish_time == other.finish_time && self.proc_id == other.proc_id
+    }
+}
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.finish_time.cmp(&other.finish_time) {
+            Ordering::Equal => self.proc_id.cmp(&other.proc_id),
+            ord => ord,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Process {
+    pub id: usize,
+    pub name: String,
+    pub needs: Vec<Stock>,
+    pub results: Vec<Stock>,
+    pub duration: i64,
+}
+
+impl Process {
+    pub(crate) fn new(
+        id: usize,
+        name: &str,
+        needs: Vec<Stock>,
+        results: Vec<Stock>,
+        duration: i64,
+    ) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            needs,
+            results,
+            duration,
+        }
+    }
+}
+
+/// A parsed scenario: its processes, the initial quantity of every stock and what to optimize.
+#[derive(Debug, Clone)]
+pub struct Spec {
+    pub processes: Vec<Process>,
+    pub init_stocks: HashMap<String, i64>,
+    /// Every stock of [`Spec::init_stocks`], in the order it first appears in the file.
+    pub stock_names: Vec<String>,
+    pub optimize: Optimize,
+}
+
+impl Spec {
+    pub(crate) fn new(
+        processes: Vec<Process>,
+        init_stocks: HashMap<String, i64>,
+        stock_names: Vec<String>,
+        optimize: Optimize,
+    ) -> Self {
+        Self {
+            processes,
+            init_stocks,
+            stock_names,
+            optimize,
+        }
+    }
+}
+
+/*
+optimized structure for simulation
+it's a little bit less readable for it is faster
+*/
+#[derive(Debug)]
+pub struct SimSpec {
+    pub(crate) needs: Vec<Vec<(usize, i64)>>, // id/qty
+    pub(crate) results: Vec<Vec<(usize, i64)>>,
+    // results minus the renewable stocks the process only hands back, what it really produces
+    pub(crate) gains: Vec<Vec<(usize, i64)>>,
+    pub(crate) durations: Vec<i64>,
+    pub(crate) init_stocks: Vec<i64>, // the idx is the id of the stock
+    pub(crate) optimize: Optimize,
+    pub(crate) target_stock_ids: Vec<usize>,
+    // renewable[stock_id], see analysis::renewable_stocks
+    pub(crate) renewable: Vec<bool>,
+    pub(crate) stock_names: Vec<String>,
+    pub(crate) process_names: Vec<String>,
+}
+
+impl SimSpec {
+    pub fn process_count(&self) -> usize {
+        self.durations.len()
+    }
+
+    pub fn stock_count(&self) -> usize {
+        self.init_stocks.len()
+    }
+
+    pub fn stock_name(&self, stock_id: usize) -> &str {
+        &self.stock_names[stock_id]
+    }
+
+    pub fn process_name(&self, proc_id: usize) -> &str {
+        &self.process_names[proc_id]
+    }
+
+    /// True when every process needing the stock gives it back, like a tool.
+    pub fn is_renewable(&self, stock_id: usize) -> bool {
+        self.renewable[stock_id]
+    }
+
+    /// Flattens a parsed [`Spec`] into the id based structure used by the simulator.
+    ///
+    /// Process ids are the indices of [`Spec::processes`] and stock ids the indices of
+    /// [`Spec::stock_names`].
+    pub fn from_spec(spec: &Spec) -> Self {
+        let targets = match &spec.optimize {
+            Optimize::Quantity(names) | Optimize::Time(names) => names,
+        };
+
+        // stock ids follow the order of appearance in the file, so they are the same on every run
+        let init_stocks_name_to_id: HashMap<String, usize> = spec
+            .stock_names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| (name.clone(), idx))
+            .collect();
+
+        // a target named twice still counts once
+        let mut target_stock_ids: Vec<usize> = vec![];
+        for name in targets {
+            if let Some(&id) = init_stocks_name_to_id.get(name)
+                && !target_stock_ids.contains(&id)
+            {
+                target_stock_ids.push(id);
+            }
+        }
+
+        let init_stocks: Vec<i64> = spec
+            .stock_names
+            .iter()
+            .map(|name| spec.init_stocks[name])
+            .collect();
+        let stock_names = spec.stock_names.clone();
+
+        let build_vec = |p: &Vec<Stock>| {
+            p.iter()
+                .map(|s| (init_stocks_name_to_id[&s.name], s.quantity))
+                .collect()
+        };
+
+        let needs: Vec<Vec<(usize, i64)>> =
+            spec.processes.iter().map(|p| build_vec(&p.needs)).collect();
+
+        let results: Vec<Vec<(usize, i64)>> = spec
+            .processes
+            .iter()
+            .map(|p| build_vec(&p.results))
+            .collect();
+
+        let renewable_names = analysis::renewable_stocks(spec);
+        let mut renewable = vec![false; init_stocks.len()];
+        for name in &renewable_names {
+            renewable[init_stocks_name_to_id[name]] = true;
+        }
+
+        let gains: Vec<Vec<(usize, i64)>> = results
+            .iter()
+            .zip(&needs)
+            .map(|(res, need)| {
+                res.iter()
+                    .filter(|r| !(renewable[r.0] && need.iter().any(|n| n.0 == r.0)))
+                    .copied()
+                    .collect()
+            })
+            .collect();
+
+        let durations = spec.processes.iter().map(|p| p.duration).collect();
+        let process_names = spec.processes.iter().map(|p| p.name.clone()).collect();
+
+        Self {
+            needs,
+            results,
+            gains,
+            durations,
+            init_stocks,
+            optimize: spec.optimize.clone(),
+            target_stock_ids,
+            renewable,
+            stock_names,
+            process_names,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::Spec;
+use crate::ga::Sim;
+
+/*
+the schedule of a simulation in the krpsim trace format,
+one `cycle:process_name` line per job start, followed by the final stocks
+*/
+pub fn format_trace(spec: &Spec, sim: &Sim) -> String {
+    let mut out = String::from("Main walk\n");
+    for &(cycle, pid) in &sim.trace {
+        out += &format!("{}:{}\n", cycle, spec.processes[pid].name);
+    }
+
+    if sim.halted {
+        out += &format!("no more process doable at time {}\n", sim.time);
+    } else {
+        out += &format!("horizon reached at time {}\n", sim.time);
+    }
+
+    // stock ids in SimSpec are the indices of stock_names
+    out += "Stock :\n";
+    for (name, qty) in spec.stock_names.iter().zip(&sim.stocks) {
+        out += &format!(" {} => {}\n", name, qty);
+    }
+    out
+}
+
+/*
+parses a trace made of `cycle:process_name` lines
+lines that do not start with a cycle number (headers, final stocks, ...) are skipped
+so the output of KRPSim can be given as is
+returns (line number, cycle, process name)
+*/
+pub fn parse_trace(input: &str) -> Result<Vec<(usize, i64, &str)>, String> {
+    let mut entries = vec![];
+
+    for (line_nbr, line) in input.lines().enumerate() {
+        let Some((cycle_str, name)) = line.trim().split_once(':') else {
+            continue;
+        };
+
+        let Ok(cycle) = cycle_str.parse::<i64>() else {
+            continue;
+        };
+
+        if cycle < 0 {
+            return Err(format!("line {} : negative cycle {}", line_nbr + 1, cycle));
+        }
+
+        if name.is_empty() {
+            return Err(format!("line {} : missing process name", line_nbr + 1));
+        }
+
+        entries.push((line_nbr + 1, cycle, name));
+    }
+
+    Ok(entries)
+}
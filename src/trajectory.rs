@@ -0,0 +1,400 @@
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::SimSpec;
+use crate::cache::FitnessCache;
+use crate::config::{GaConfig, RunOptions};
+use crate::decoder::Decoder;
+use crate::ga::{
+    DEFAULT_GENERATIONS, Genome, Score, StopReason, eval_fitness, gen_random_genome, island_rng,
+    reached,
+};
+use crate::memetic::{Move, apply, moves};
+use crate::progress::Progress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptimizerKind {
+    #[default]
+    Ga,
+    Annealing,
+    Tabu,
+}
+
+impl OptimizerKind {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "ga" => Ok(OptimizerKind::Ga),
+            "sa" => Ok(OptimizerKind::Annealing),
+            "tabu" => Ok(OptimizerKind::Tabu),
+            _ => Err(format!("Invalid optimizer {}, expected ga, sa or tabu", s)),
+        }
+    }
+}
+
+impl fmt::Display for OptimizerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            OptimizerKind::Ga => "ga",
+            OptimizerKind::Annealing => "sa",
+            OptimizerKind::Tabu => "tabu",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// how the temperature of the annealing goes down over the evaluation budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cooling {
+    #[default]
+    Geometric,
+    Linear,
+}
+
+impl Cooling {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "geometric" => Ok(Cooling::Geometric),
+            "linear" => Ok(Cooling::Linear),
+            _ => Err(format!(
+                "Invalid cooling {}, expected geometric or linear",
+                s
+            )),
+        }
+    }
+
+    // the temperature once `progress` (0 to 1) of the budget is spent
+    fn temperature(self, start: f64, end: f64, progress: f64) -> f64 {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            Cooling::Geometric => start * (end / start).powf(progress),
+            Cooling::Linear => start + (end - start) * progress,
+        }
+    }
+}
+
+impl fmt::Display for Cooling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Cooling::Geometric => "geometric",
+            Cooling::Linear => "linear",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/*
+the bookkeeping shared by the single trajectory optimizers
+they get the evaluations the GA would make, max_population per generation,
+so every max_population evaluations count as a generation for the progress and the stopping criteria
+*/
+struct Run<'a> {
+    spec: &'a SimSpec,
+    decoder: Box<dyn Decoder>,
+    cache: FitnessCache,
+    options: &'a RunOptions,
+    progress: &'a mut Progress,
+    t0: Instant,
+    per_generation: usize,
+    evals: usize,
+    generation: usize,
+    best: Genome,
+    // best fitness at the end of the last generation, and generations since it last grew
+    generation_best: i64,
+    stale_generations: usize,
+    stop: Option<StopReason>,
+}
+
+impl<'a> Run<'a> {
+    fn new(
+        spec: &'a SimSpec,
+        config: &GaConfig,
+        options: &'a RunOptions,
+        progress: &'a mut Progress,
+    ) -> Self {
+        // the GA's first generation, the trajectory starts from its best genome
+        let mut rng = island_rng(options.seed, 0, 0);
+        let mut sample: Vec<Genome> = (0..config.max_population)
+            .map(|_| gen_random_genome(spec.process_count(), &mut rng))
+            .collect();
+
+        let mut run = Run {
+            spec,
+            decoder: config.decoder(),
+            cache: FitnessCache::new(config.cache_size),
+            options,
+            progress,
+            t0: Instant::now(),
+            per_generation: config.max_population,
+            evals: 0,
+            generation: 0,
+            best: Genome {
+                fitness: i64::MIN,
+                ..sample[0].clone()
+            },
+            generation_best: i64::MIN,
+            stale_generations: 0,
+            stop: None,
+        };
+        run.eval(&mut sample);
+        run
+    }
+
+    // evaluates the genomes and keeps the best, then checks every stopping criterion
+    fn eval(&mut self, genomes: &mut [Genome]) {
+        let (spec, decoder, horizon) = (self.spec, self.decoder.as_ref(), self.options.horizon);
+        let cache = &self.cache;
+        genomes.par_iter_mut().for_each(|g| {
+            cache.fitness(g, |g| eval_fitness(spec, decoder, g, horizon).0);
+        });
+        self.evals += genomes.len();
+
+        for g in genomes.iter() {
+            if g.fitness > self.best.fitness {
+                self.best = g.clone();
+            }
+        }
+        if self.stop.is_none() {
+            self.stop = reached(self.spec, self.options, self.best.fitness);
+        }
+
+        while self.evals >= (self.generation + 1) * self.per_generation {
+            self.end_generation();
+        }
+
+        if self.stop.is_none()
+            && self
+                .options
+                .time_budget
+                .is_some_and(|budget| self.t0.elapsed().as_secs_f64() >= budget)
+        {
+            self.stop = Some(StopReason::TimeBudget);
+        }
+        if self.stop.is_none()
+            && self
+                .options
+                .interrupt
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            self.stop = Some(StopReason::Interrupted);
+        }
+    }
+
+    fn end_generation(&mut self) {
+        self.progress.info(format_args!(
+            "generation {} : best fitness {} ({:.3}s)",
+            self.generation,
+            Score::of(self.spec, self.best.fitness),
+            self.progress.elapsed()
+        ));
+        if self.best.fitness > self.generation_best {
+            self.generation_best = self.best.fitness;
+            self.stale_generations = 0;
+        } else {
+            self.stale_generations += 1;
+            if self.stop.is_none()
+                && self
+                    .options
+                    .stagnation
+                    .is_some_and(|n| self.stale_generations >= n)
+            {
+                self.stop = Some(StopReason::Stagnation);
+            }
+        }
+        self.generation += 1;
+        if self.stop.is_none()
+            && self.options.generations != 0
+            && self.generation >= self.options.generations
+        {
+            self.stop = Some(StopReason::Generations);
+        }
+    }
+
+    // evaluations left before the generation limit, all of them without one
+    fn remaining(&self) -> usize {
+        match self.options.generations {
+            0 => usize::MAX,
+            n => (n * self.per_generation).saturating_sub(self.evals),
+        }
+    }
+
+    /*
+    share of the budget spent, for the cooling
+    without a generation limit the time budget is used, and failing that the default generations
+    */
+    fn spent(&self) -> f64 {
+        if self.options.generations == 0
+            && let Some(budget) = self.options.time_budget
+        {
+            return self.t0.elapsed().as_secs_f64() / budget;
+        }
+        let generations = match self.options.generations {
+            0 => DEFAULT_GENERATIONS,
+            n => n,
+        };
+        self.evals as f64 / (generations * self.per_generation) as f64
+    }
+
+    fn finish(self) -> (Genome, StopReason) {
+        let stop = self.stop.unwrap_or(StopReason::Generations);
+        let generations =
+            self.generation + !self.evals.is_multiple_of(self.per_generation) as usize;
+        self.progress.stopped(generations, stop, self.cache.stats());
+        (self.best, stop)
+    }
+}
+
+/*
+the log of the chance to take a worse genome : delta / (temperature * |current|), the temperature
+being a share of the current value so that it means the same on every scenario
+for Time the values are the cycles the targets last grew at, or the quantities when those are equal,
+never the packed fitness whose cycle part would dwarf any temperature
+*/
+fn worse_log_chance(spec: &SimSpec, current: i64, cand: i64, temperature: f64) -> f64 {
+    let (current, cand) = (Score::of(spec, current), Score::of(spec, cand));
+    let (delta, value) = match (current.cycle, cand.cycle) {
+        (Some(current), Some(cand)) if current != cand => (current - cand, current),
+        _ => (cand.quantity - current.quantity, current.quantity),
+    };
+    delta as f64 / (temperature * value.unsigned_abs().max(1) as f64)
+}
+
+/*
+simulated annealing from the best of a random generation
+every step tries a random move, taken if it does better, or with a chance of
+exp(worse_log_chance) if it does worse
+*/
+pub fn anneal(
+    spec: &SimSpec,
+    config: &GaConfig,
+    options: &RunOptions,
+    progress: &mut Progress,
+) -> (Genome, StopReason) {
+    let mut rng = island_rng(options.seed, 0, 1);
+    let mut run = Run::new(spec, config, options, progress);
+    let mut current = run.best.clone();
+
+    while run.stop.is_none() {
+        let mvs = moves(&current);
+        if mvs.is_empty() {
+            break;
+        }
+        let mut cand = [apply(&current, mvs[rng.random_range(0..mvs.len())])];
+        run.eval(&mut cand);
+        let [cand] = cand;
+
+        let temperature = config.sa_cooling.temperature(
+            config.sa_temperature,
+            config.sa_final_temperature,
+            run.spent(),
+        );
+        // the draw is made every step so that the rng doesn't depend on the fitness
+        let draw = rng.random::<f64>();
+        if cand.fitness >= current.fitness
+            || draw < worse_log_chance(spec, current.fitness, cand.fitness, temperature).exp()
+        {
+            current = cand;
+        }
+    }
+    run.finish()
+}
+
+// the processes a move touches, the divider being one more after them
+fn attributes(mv: Move, process_count: usize) -> Vec<usize> {
+    match mv {
+        Move::Swap(a, b) => vec![a, b],
+        Move::Toggle(pid) => vec![pid],
+        Move::Divider(_) => vec![process_count],
+    }
+}
+
+/*
+tabu search from the best of a random generation
+every iteration evaluates the whole neighbourhood and moves to its best genome, even a worse one,
+except through the processes touched in the last `tabu_tenure` iterations
+a tabu move is still taken when it beats the best genome found so far,
+and when every move is tabu the one freed the soonest is taken
+ties are drawn at random, on a plateau always taking the first would go back and forth
+*/
+pub fn tabu(
+    spec: &SimSpec,
+    config: &GaConfig,
+    options: &RunOptions,
+    progress: &mut Progress,
+) -> (Genome, StopReason) {
+    let n = spec.process_count();
+    let mut rng = island_rng(options.seed, 0, 1);
+    let mut run = Run::new(spec, config, options, progress);
+    let mut current = run.best.clone();
+    // iteration until which each attribute is tabu
+    let mut tabu_until = vec![0usize; n + 1];
+    let mut iteration = 0;
+
+    while run.stop.is_none() {
+        iteration += 1;
+        let mut mvs = moves(&current);
+        mvs.truncate(run.remaining());
+        if mvs.is_empty() {
+            break;
+        }
+        let mut cands: Vec<Genome> = mvs.iter().map(|&mv| apply(&current, mv)).collect();
+        let best_before = run.best.fitness;
+        run.eval(&mut cands);
+
+        // the iteration until which a move stays tabu
+        let freed = |i: usize| {
+            attributes(mvs[i], n)
+                .iter()
+                .map(|&a| tabu_until[a])
+                .max()
+                .unwrap_or(0)
+        };
+        let mut allowed: Vec<usize> = (0..cands.len())
+            .filter(|&i| cands[i].fitness > best_before || freed(i) < iteration)
+            .collect();
+        if allowed.is_empty() {
+            let soonest = (0..cands.len()).map(freed).min().unwrap_or(0);
+            allowed = (0..cands.len()).filter(|&i| freed(i) == soonest).collect();
+        }
+        let top = allowed.iter().map(|&i| cands[i].fitness).max().unwrap_or(0);
+        allowed.retain(|&i| cands[i].fitness == top);
+        let chosen = allowed[rng.random_range(0..allowed.len())];
+
+        for a in attributes(mvs[chosen], n) {
+            tabu_until[a] = iteration + config.tabu_tenure;
+        }
+        current = cands.swap_remove(chosen);
+    }
+    run.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ga::pack_time_fitness;
+    use crate::parse;
+
+    fn sim_spec(input: &str) -> SimSpec {
+        SimSpec::from_spec(&parse(input).expect("invalid scenario"))
+    }
+
+    #[test]
+    fn later_finish_is_rejected_when_cold() {
+        let spec = sim_spec(include_str!("../input_files/steak"));
+        let (current, cand) = (pack_time_fitness(2, 20), pack_time_fitness(2, 30));
+        assert!(worse_log_chance(&spec, current, cand, 0.0005).exp() < 1e-6);
+        // hot, the same move is a fair bet
+        assert!(worse_log_chance(&spec, current, cand, 1.0).exp() > 0.5);
+    }
+
+    #[test]
+    fn fewer_targets_by_the_same_cycle_are_rejected_when_cold() {
+        let spec = sim_spec(include_str!("../input_files/steak"));
+        let (current, cand) = (pack_time_fitness(3, 20), pack_time_fitness(2, 20));
+        assert!(worse_log_chance(&spec, current, cand, 0.0005).exp() < 1e-6);
+    }
+}
@@ -0,0 +1,106 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::Spec;
+use crate::spec::Job;
+use crate::trace::parse_trace;
+
+/*
+last_start is the cycle of the last line of the trace, the one KRPSim calls its last cycle
+last_finish is the cycle the last job is done, the stocks are the ones at that point
+*/
+pub struct VerifReport {
+    pub starts: usize,
+    pub last_start: i64,
+    pub last_finish: i64,
+    pub stocks: HashMap<String, i64>,
+}
+
+fn complete_jobs_until(
+    spec: &Spec,
+    cycle: i64,
+    running: &mut BinaryHeap<Reverse<Job>>,
+    stocks: &mut HashMap<String, i64>,
+) {
+    while let Some(Reverse(job)) = running.peek() {
+        if job.finish_time > cycle {
+            break;
+        }
+        let Reverse(job) = running.pop().unwrap();
+
+        for r in &spec.processes[job.proc_id].results {
+            *stocks.entry(r.name.clone()).or_insert(0) += r.quantity;
+        }
+    }
+}
+
+/*
+replays the trace against the spec, the same way the simulator does:
+every job finishing at or before a cycle gives back its results before anything starts at that cycle
+*/
+pub fn verify(spec: &Spec, trace: &str) -> Result<VerifReport, String> {
+    let entries = parse_trace(trace)?;
+
+    let name_to_id: HashMap<&str, usize> = spec
+        .processes
+        .iter()
+        .enumerate()
+        .map(|(idx, p)| (p.name.as_str(), idx))
+        .collect();
+
+    let mut stocks = spec.init_stocks.clone();
+    let mut running: BinaryHeap<Reverse<Job>> = BinaryHeap::new();
+    let mut last_start = 0;
+
+    for &(line_nbr, cycle, name) in &entries {
+        if cycle < last_start {
+            return Err(format!(
+                "line {} : cycle {} goes backwards, previous start was at cycle {}",
+                line_nbr, cycle, last_start
+            ));
+        }
+
+        let &pid = name_to_id
+            .get(name)
+            .ok_or_else(|| format!("line {} : unknown process {}", line_nbr, name))?;
+
+        complete_jobs_until(spec, cycle, &mut running, &mut stocks);
+
+        let process = &spec.processes[pid];
+        for need in &process.needs {
+            let have = stocks.get(&need.name).copied().unwrap_or(0);
+            if have < need.quantity {
+                return Err(format!(
+                    "line {} : {} can't start at cycle {}, needs {} {} but only {} in stock",
+                    line_nbr, name, cycle, need.quantity, need.name, have
+                ));
+            }
+        }
+
+        for need in &process.needs {
+            *stocks.get_mut(&need.name).unwrap() -= need.quantity;
+        }
+
+        running.push(Reverse(Job {
+            finish_time: cycle + process.duration,
+            proc_id: pid,
+        }));
+
+        last_start = cycle;
+    }
+
+    let last_finish = running
+        .iter()
+        .map(|Reverse(job)| job.finish_time)
+        .max()
+        .unwrap_or(0)
+        .max(last_start);
+    complete_jobs_until(spec, i64::MAX, &mut running, &mut stocks);
+
+    Ok(VerifReport {
+        starts: entries.len(),
+        last_start,
+        last_finish,
+        stocks,
+    })
+}